@@ -0,0 +1,506 @@
+// Persistent audit sink
+// ---------------------
+// `analyze_for_threats` produces rich `ThreatEvent` records that were
+// previously dropped on the floor — nothing persisted them anywhere. This
+// gives them (and connection lifecycle records) a home: an async
+// `ThreatEventSink` trait, fed over an `mpsc` channel so `handle_client`
+// and `analyze_for_threats` never block on I/O, with a Postgres/
+// TimescaleDB-backed implementation that batches multi-row inserts and
+// spills overflow to a local JSONL file when the database is unreachable.
+
+use crate::ThreatEvent;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::{mpsc, Mutex};
+use tokio::time::{interval_at, Instant, MissedTickBehavior};
+use tokio_postgres::types::ToSql;
+use tokio_postgres::{Client, NoTls};
+
+/// Connection lifecycle record. Deliberately independent of the server's
+/// in-memory `Connection` struct in `main.rs` so this module doesn't need
+/// to depend on the binary crate's internals.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionRecord {
+    pub id: String,
+    pub peer_addr: String,
+    pub connected_at: DateTime<Utc>,
+    pub disconnected_at: Option<DateTime<Utc>>,
+    pub bytes_received: u64,
+    pub bytes_sent: u64,
+}
+
+/// One unit of work flowing through the audit channel.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum AuditRecord {
+    Threat(ThreatEvent),
+    Connection(ConnectionRecord),
+}
+
+/// DDL for the two hypertables. Not run automatically — an operator (or
+/// deployment migration step) applies this once against a TimescaleDB
+/// instance before the sink is started.
+pub const THREAT_EVENTS_SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS threat_events (
+    timestamp   TIMESTAMPTZ NOT NULL,
+    event_id    TEXT NOT NULL,
+    source_ip   TEXT NOT NULL,
+    event_type  TEXT NOT NULL,
+    description TEXT NOT NULL,
+    severity    TEXT NOT NULL,
+    fingerprint TEXT,
+    metadata    JSONB NOT NULL
+);
+SELECT create_hypertable('threat_events', 'timestamp', if_not_exists => TRUE);
+";
+
+pub const CONNECTIONS_SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS connections (
+    connected_at    TIMESTAMPTZ NOT NULL,
+    id              TEXT NOT NULL,
+    peer_addr       TEXT NOT NULL,
+    disconnected_at TIMESTAMPTZ,
+    bytes_received  BIGINT NOT NULL,
+    bytes_sent      BIGINT NOT NULL
+);
+SELECT create_hypertable('connections', 'connected_at', if_not_exists => TRUE);
+";
+
+const DEFAULT_BATCH_SIZE: usize = 128;
+const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+const DEFAULT_CHANNEL_CAPACITY: usize = 4096;
+const INITIAL_RECONNECT_DELAY: Duration = Duration::from_millis(500);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+pub enum AuditError {
+    Connect(String),
+    Query(String),
+    Spill(String),
+}
+
+impl fmt::Display for AuditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuditError::Connect(msg) => write!(f, "failed to connect to audit database: {msg}"),
+            AuditError::Query(msg) => write!(f, "audit batch insert failed: {msg}"),
+            AuditError::Spill(msg) => write!(f, "failed to spill audit overflow to disk: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for AuditError {}
+
+/// Anything that can durably persist a batch of audit records. Kept as a
+/// trait (rather than hard-coding Postgres into the flush loop) so tests
+/// and future sinks (e.g. a different time-series store) can swap in
+/// without touching the batching/backoff machinery.
+#[async_trait]
+pub trait ThreatEventSink: Send + Sync {
+    async fn persist_batch(&self, records: &[AuditRecord]) -> Result<(), AuditError>;
+}
+
+/// Postgres/TimescaleDB-backed sink. Holds a lazily-(re)established
+/// connection behind a mutex; a failed insert drops the connection so the
+/// next flush attempt reconnects rather than retrying a poisoned session.
+pub struct PostgresSink {
+    connection_string: String,
+    client: Mutex<Option<Client>>,
+    reconnect_delay: Mutex<Duration>,
+}
+
+impl PostgresSink {
+    pub fn new(connection_string: impl Into<String>) -> Self {
+        Self {
+            connection_string: connection_string.into(),
+            client: Mutex::new(None),
+            reconnect_delay: Mutex::new(INITIAL_RECONNECT_DELAY),
+        }
+    }
+
+    /// Make one connection attempt. The exponential backoff between failed
+    /// attempts (capped at `MAX_RECONNECT_DELAY`) is applied by
+    /// `note_connect_failure`; a success resets it so a brief blip doesn't
+    /// leave future reconnects artificially slow.
+    async fn connect_with_backoff(&self) -> Result<Client, AuditError> {
+        let (client, connection) = tokio_postgres::connect(&self.connection_string, NoTls)
+            .await
+            .map_err(|e| {
+                AuditError::Connect(e.to_string())
+            })?;
+
+        // The connection object drives the actual socket I/O; it must be
+        // polled concurrently or the client will never make progress.
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                log::error!("audit sink connection closed with error: {e}");
+            }
+        });
+
+        *self.reconnect_delay.lock().await = INITIAL_RECONNECT_DELAY;
+        Ok(client)
+    }
+
+    async fn note_connect_failure(&self) {
+        let mut delay = self.reconnect_delay.lock().await;
+        tokio::time::sleep(*delay).await;
+        *delay = (*delay * 2).min(MAX_RECONNECT_DELAY);
+    }
+
+    async fn insert_threats(&self, client: &Client, events: &[&ThreatEvent]) -> Result<(), AuditError> {
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let mut query = String::from(
+            "INSERT INTO threat_events (timestamp, event_id, source_ip, event_type, description, severity, fingerprint, metadata) VALUES ",
+        );
+        // `ThreatEvent` doesn't carry fingerprint as its own field — the
+        // originating `ProtocolMessage`'s fingerprint rides along inside
+        // `metadata` (see `protocol::analyze_for_threats`), so pull it back
+        // out for its own indexed column.
+        let fingerprints: Vec<Option<String>> = events
+            .iter()
+            .map(|event| {
+                event
+                    .metadata
+                    .get("fingerprint")
+                    .and_then(|v| v.as_str())
+                    .map(str::to_string)
+            })
+            .collect();
+        let mut params: Vec<&(dyn ToSql + Sync)> = Vec::with_capacity(events.len() * 8);
+        for (i, event) in events.iter().enumerate() {
+            if i > 0 {
+                query.push(',');
+            }
+            let base = i * 8;
+            query.push_str(&format!(
+                "(${}, ${}, ${}, ${}, ${}, ${}, ${}, ${})",
+                base + 1,
+                base + 2,
+                base + 3,
+                base + 4,
+                base + 5,
+                base + 6,
+                base + 7,
+                base + 8,
+            ));
+            params.push(&event.timestamp);
+            params.push(&event.event_id);
+            params.push(&event.source_ip);
+            params.push(&event.event_type);
+            params.push(&event.description);
+            params.push(&event.severity);
+            params.push(&fingerprints[i]);
+            params.push(&event.metadata);
+        }
+
+        client
+            .execute(query.as_str(), &params)
+            .await
+            .map(|_| ())
+            .map_err(|e| AuditError::Query(e.to_string()))
+    }
+
+    async fn insert_connections(
+        &self,
+        client: &Client,
+        records: &[&ConnectionRecord],
+    ) -> Result<(), AuditError> {
+        if records.is_empty() {
+            return Ok(());
+        }
+
+        let mut query = String::from(
+            "INSERT INTO connections (connected_at, id, peer_addr, disconnected_at, bytes_received, bytes_sent) VALUES ",
+        );
+        // Postgres has no unsigned integer types, so the u64 byte counters
+        // need an i64 cast; the casts are collected into owned vecs first
+        // since `params` borrows for the lifetime of the query below and a
+        // cast's temporary wouldn't outlive this loop.
+        let bytes_received: Vec<i64> = records.iter().map(|r| r.bytes_received as i64).collect();
+        let bytes_sent: Vec<i64> = records.iter().map(|r| r.bytes_sent as i64).collect();
+        let mut params: Vec<&(dyn ToSql + Sync)> = Vec::with_capacity(records.len() * 6);
+        for (i, record) in records.iter().enumerate() {
+            if i > 0 {
+                query.push(',');
+            }
+            let base = i * 6;
+            query.push_str(&format!(
+                "(${}, ${}, ${}, ${}, ${}, ${})",
+                base + 1,
+                base + 2,
+                base + 3,
+                base + 4,
+                base + 5,
+                base + 6,
+            ));
+            params.push(&record.connected_at);
+            params.push(&record.id);
+            params.push(&record.peer_addr);
+            params.push(&record.disconnected_at);
+            params.push(&bytes_received[i]);
+            params.push(&bytes_sent[i]);
+        }
+
+        client
+            .execute(query.as_str(), &params)
+            .await
+            .map(|_| ())
+            .map_err(|e| AuditError::Query(e.to_string()))
+    }
+}
+
+#[async_trait]
+impl ThreatEventSink for PostgresSink {
+    async fn persist_batch(&self, records: &[AuditRecord]) -> Result<(), AuditError> {
+        let mut guard = self.client.lock().await;
+        if guard.is_none() {
+            match self.connect_with_backoff().await {
+                Ok(client) => *guard = Some(client),
+                Err(e) => {
+                    drop(guard);
+                    self.note_connect_failure().await;
+                    return Err(e);
+                }
+            }
+        }
+        let client = guard.as_ref().expect("client set above");
+
+        let threats: Vec<&ThreatEvent> = records
+            .iter()
+            .filter_map(|r| match r {
+                AuditRecord::Threat(t) => Some(t),
+                AuditRecord::Connection(_) => None,
+            })
+            .collect();
+        let connections: Vec<&ConnectionRecord> = records
+            .iter()
+            .filter_map(|r| match r {
+                AuditRecord::Connection(c) => Some(c),
+                AuditRecord::Threat(_) => None,
+            })
+            .collect();
+
+        let result = async {
+            self.insert_threats(client, &threats).await?;
+            self.insert_connections(client, &connections).await
+        }
+        .await;
+
+        if result.is_err() {
+            // Drop the (possibly broken) connection so the next flush
+            // reconnects instead of repeatedly failing on a dead session.
+            *guard = None;
+        }
+
+        result
+    }
+}
+
+/// Drives the buffer/flush loop for one sink: accumulate records until
+/// `batch_size` is reached or `flush_interval` elapses, persist the batch,
+/// and spill to `spill_path` (append-only JSONL) when persistence fails so
+/// nothing is silently lost.
+pub struct AuditWorker<S: ThreatEventSink> {
+    sink: S,
+    batch_size: usize,
+    flush_interval: Duration,
+    spill_path: PathBuf,
+}
+
+impl<S: ThreatEventSink> AuditWorker<S> {
+    pub fn new(sink: S, spill_path: impl Into<PathBuf>) -> Self {
+        Self {
+            sink,
+            batch_size: DEFAULT_BATCH_SIZE,
+            flush_interval: DEFAULT_FLUSH_INTERVAL,
+            spill_path: spill_path.into(),
+        }
+    }
+
+    async fn flush(&self, buffer: &mut Vec<AuditRecord>) {
+        if buffer.is_empty() {
+            return;
+        }
+
+        if let Err(e) = self.sink.persist_batch(buffer).await {
+            log::warn!("audit flush failed, spilling {} record(s) to disk: {e}", buffer.len());
+            if let Err(spill_err) = self.spill(buffer).await {
+                log::error!("audit overflow spill also failed: {spill_err}");
+            }
+        }
+
+        buffer.clear();
+    }
+
+    async fn spill(&self, buffer: &[AuditRecord]) -> Result<(), AuditError> {
+        use tokio::io::AsyncWriteExt;
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.spill_path)
+            .await
+            .map_err(|e| AuditError::Spill(e.to_string()))?;
+
+        for record in buffer {
+            let line = serde_json::to_string(record).map_err(|e| AuditError::Spill(e.to_string()))?;
+            file.write_all(line.as_bytes())
+                .await
+                .map_err(|e| AuditError::Spill(e.to_string()))?;
+            file.write_all(b"\n").await.map_err(|e| AuditError::Spill(e.to_string()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Run the receive/batch/flush loop until the channel is closed,
+    /// flushing whatever remains buffered on shutdown.
+    pub async fn run(mut self, mut rx: mpsc::Receiver<AuditRecord>) {
+        let mut buffer = Vec::with_capacity(self.batch_size);
+        // Start the first tick one interval out; a plain `interval` fires
+        // immediately and would flush a partial batch at startup.
+        let mut ticker = interval_at(Instant::now() + self.flush_interval, self.flush_interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                received = rx.recv() => {
+                    match received {
+                        Some(record) => {
+                            buffer.push(record);
+                            if buffer.len() >= self.batch_size {
+                                self.flush(&mut buffer).await;
+                            }
+                        }
+                        None => {
+                            self.flush(&mut buffer).await;
+                            break;
+                        }
+                    }
+                }
+                _ = ticker.tick() => {
+                    self.flush(&mut buffer).await;
+                }
+            }
+        }
+    }
+}
+
+/// Spawn the audit worker as its own tokio task and return the sender
+/// half, so `handle_client`/`analyze_for_threats` can hand off records
+/// with a non-blocking `try_send` and never wait on database I/O.
+pub fn spawn<S: ThreatEventSink + 'static>(
+    sink: S,
+    spill_path: impl Into<PathBuf>,
+) -> (mpsc::Sender<AuditRecord>, tokio::task::JoinHandle<()>) {
+    let (tx, rx) = mpsc::channel(DEFAULT_CHANNEL_CAPACITY);
+    let worker = AuditWorker::new(sink, spill_path);
+    let handle = tokio::spawn(worker.run(rx));
+    (tx, handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn sample_threat(id: &str) -> ThreatEvent {
+        ThreatEvent {
+            event_id: id.to_string(),
+            timestamp: Utc::now(),
+            source_ip: "203.0.113.7".to_string(),
+            event_type: "reconnaissance".to_string(),
+            description: "test event".to_string(),
+            severity: "low".to_string(),
+            metadata: serde_json::json!({"fingerprint": "abc123"}),
+        }
+    }
+
+    /// In-memory sink that records every batch it receives, for exercising
+    /// `AuditWorker`'s batching/flush logic without a real database.
+    struct RecordingSink {
+        batches: Arc<std::sync::Mutex<Vec<Vec<AuditRecord>>>>,
+    }
+
+    #[async_trait]
+    impl ThreatEventSink for RecordingSink {
+        async fn persist_batch(&self, records: &[AuditRecord]) -> Result<(), AuditError> {
+            self.batches.lock().unwrap().push(records.to_vec());
+            Ok(())
+        }
+    }
+
+    struct FailingSink;
+
+    #[async_trait]
+    impl ThreatEventSink for FailingSink {
+        async fn persist_batch(&self, _records: &[AuditRecord]) -> Result<(), AuditError> {
+            Err(AuditError::Connect("refused".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn flushes_on_batch_size() {
+        let batches = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = RecordingSink { batches: batches.clone() };
+        let mut worker = AuditWorker::new(sink, std::env::temp_dir().join("apate_audit_test_batch.jsonl"));
+        worker.batch_size = 2;
+
+        let (tx, rx) = mpsc::channel(16);
+        let handle = tokio::spawn(worker.run(rx));
+
+        tx.send(AuditRecord::Threat(sample_threat("a"))).await.unwrap();
+        tx.send(AuditRecord::Threat(sample_threat("b"))).await.unwrap();
+        drop(tx);
+        handle.await.unwrap();
+
+        let flushed = batches.lock().unwrap();
+        assert_eq!(flushed.len(), 1);
+        assert_eq!(flushed[0].len(), 2);
+    }
+
+    #[tokio::test]
+    async fn flushes_remaining_buffer_on_channel_close() {
+        let batches = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = RecordingSink { batches: batches.clone() };
+        let mut worker = AuditWorker::new(sink, std::env::temp_dir().join("apate_audit_test_close.jsonl"));
+        worker.batch_size = 128;
+
+        let (tx, rx) = mpsc::channel(16);
+        let handle = tokio::spawn(worker.run(rx));
+
+        tx.send(AuditRecord::Threat(sample_threat("only"))).await.unwrap();
+        drop(tx);
+        handle.await.unwrap();
+
+        let flushed = batches.lock().unwrap();
+        assert_eq!(flushed.len(), 1);
+        assert_eq!(flushed[0].len(), 1);
+    }
+
+    #[tokio::test]
+    async fn spills_to_disk_when_sink_fails() {
+        let spill_path = std::env::temp_dir().join("apate_audit_test_spill.jsonl");
+        let _ = std::fs::remove_file(&spill_path);
+
+        let mut worker = AuditWorker::new(FailingSink, spill_path.clone());
+        worker.batch_size = 1;
+
+        let (tx, rx) = mpsc::channel(16);
+        let handle = tokio::spawn(worker.run(rx));
+
+        tx.send(AuditRecord::Threat(sample_threat("spill-me"))).await.unwrap();
+        drop(tx);
+        handle.await.unwrap();
+
+        let contents = std::fs::read_to_string(&spill_path).unwrap();
+        assert!(contents.contains("spill-me"));
+        let _ = std::fs::remove_file(&spill_path);
+    }
+}
@@ -0,0 +1,270 @@
+// Adaptive ban subsystem
+// ----------------------
+// Turns repeated ThreatEvents into actual block decisions, fail2ban-style:
+// a sliding `findtime` window of recent offenses, a `maxretry` threshold,
+// and an escalating `bantime` for repeat offenders (exponential backoff,
+// capped). Never bans anything in the allowlist.
+
+use crate::ThreatEvent;
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A CIDR block used for the allowlist (e.g. "10.0.0.0/8", "::1/128").
+#[derive(Debug, Clone, Copy)]
+pub struct CidrBlock {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl CidrBlock {
+    /// Check whether `ip` falls inside this block.
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(base), IpAddr::V4(candidate)) => {
+                let bits = self.prefix_len.min(32);
+                let mask = if bits == 0 { 0 } else { u32::MAX << (32 - bits) };
+                (u32::from(base) & mask) == (u32::from(*candidate) & mask)
+            }
+            (IpAddr::V6(base), IpAddr::V6(candidate)) => {
+                let bits = self.prefix_len.min(128);
+                let mask = if bits == 0 { 0 } else { u128::MAX << (128 - bits) };
+                (u128::from(base) & mask) == (u128::from(*candidate) & mask)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for CidrBlock {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr_part, prefix_part) = match s.split_once('/') {
+            Some((a, p)) => (a, p),
+            None => (s, if s.contains(':') { "128" } else { "32" }),
+        };
+        let addr = IpAddr::from_str(addr_part).map_err(|_| ())?;
+        let prefix_len: u8 = prefix_part.parse().map_err(|_| ())?;
+        Ok(CidrBlock { addr, prefix_len })
+    }
+}
+
+/// A decision to block an IP, ready to render into a firewall rule.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BanDecision {
+    pub ip: String,
+    pub until: u64,
+    pub rule_text: String,
+}
+
+impl BanDecision {
+    fn new(ip: String, until: u64) -> Self {
+        let rule_text = format!(
+            "add element inet filter apate_banned {{ {} }}",
+            ip
+        );
+        Self { ip, until, rule_text }
+    }
+}
+
+/// Per-IP offense tracking: a sliding window of event timestamps plus
+/// the running ban history used to compute the next bantime.
+struct IpState {
+    events: VecDeque<u64>,
+    prior_bans: u32,
+    banned_until: Option<u64>,
+}
+
+impl IpState {
+    fn new() -> Self {
+        Self {
+            events: VecDeque::new(),
+            prior_bans: 0,
+            banned_until: None,
+        }
+    }
+}
+
+/// Fail2ban-style adaptive ban list.
+pub struct BanList {
+    state: Mutex<HashMap<String, IpState>>,
+    allowlist: Vec<CidrBlock>,
+    findtime_ms: u64,
+    maxretry: usize,
+    base_bantime_ms: u64,
+    max_bantime_ms: u64,
+}
+
+impl BanList {
+    pub fn new(findtime_ms: u64, maxretry: usize, base_bantime_ms: u64, max_bantime_ms: u64) -> Self {
+        Self {
+            state: Mutex::new(HashMap::new()),
+            allowlist: Vec::new(),
+            findtime_ms,
+            maxretry,
+            base_bantime_ms,
+            max_bantime_ms,
+        }
+    }
+
+    /// Default tuning: 10 minute findtime, 5 retries, 1 minute base bantime
+    /// doubling up to a 24h cap.
+    pub fn with_defaults() -> Self {
+        Self::new(600_000, 5, 60_000, 24 * 60 * 60 * 1000)
+    }
+
+    pub fn with_allowlist(mut self, cidrs: &[&str]) -> Self {
+        self.allowlist = cidrs.iter().filter_map(|c| CidrBlock::from_str(c).ok()).collect();
+        self
+    }
+
+    fn is_allowlisted(&self, ip: &str) -> bool {
+        match IpAddr::from_str(ip) {
+            Ok(addr) => self.allowlist.iter().any(|block| block.contains(&addr)),
+            Err(_) => false,
+        }
+    }
+
+    /// Ingest a ThreatEvent for its source IP, returning a BanDecision if
+    /// this event pushed the IP over `maxretry` within `findtime`.
+    pub fn ingest(&self, event: &ThreatEvent) -> Option<BanDecision> {
+        if self.is_allowlisted(&event.source_ip) {
+            return None;
+        }
+
+        let now = current_time_ms();
+        let mut state = self.state.lock().unwrap();
+        let entry = state.entry(event.source_ip.clone()).or_insert_with(IpState::new);
+
+        entry.events.push_back(now);
+        let cutoff = now.saturating_sub(self.findtime_ms);
+        while matches!(entry.events.front(), Some(ts) if *ts < cutoff) {
+            entry.events.pop_front();
+        }
+
+        if entry.events.len() < self.maxretry {
+            return None;
+        }
+
+        // Already banned and still active: nothing new to decide.
+        if matches!(entry.banned_until, Some(until) if until > now) {
+            return None;
+        }
+
+        let bantime = self
+            .base_bantime_ms
+            .saturating_mul(1u64.checked_shl(entry.prior_bans).unwrap_or(u64::MAX))
+            .min(self.max_bantime_ms);
+        let until = now + bantime;
+
+        entry.banned_until = Some(until);
+        entry.prior_bans += 1;
+        entry.events.clear();
+
+        Some(BanDecision::new(event.source_ip.clone(), until))
+    }
+
+    /// Remaining ban duration in milliseconds, if `ip` is currently banned.
+    pub fn check_ban(&self, ip: &str) -> Option<u64> {
+        let now = current_time_ms();
+        let state = self.state.lock().unwrap();
+        let until = state.get(ip)?.banned_until?;
+        (until > now).then(|| until - now)
+    }
+
+    /// Drop expired bans and counters with no recent activity, so the map
+    /// doesn't grow unbounded under scanner floods.
+    pub fn evict_expired(&self) {
+        let now = current_time_ms();
+        let cutoff = now.saturating_sub(self.findtime_ms);
+        let mut state = self.state.lock().unwrap();
+        state.retain(|_, s| {
+            let ban_active = matches!(s.banned_until, Some(until) if until > now);
+            let has_recent_events = matches!(s.events.back(), Some(ts) if *ts >= cutoff);
+            ban_active || has_recent_events
+        });
+    }
+}
+
+fn current_time_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn event(ip: &str, severity: &str) -> ThreatEvent {
+        ThreatEvent {
+            event_id: "test".to_string(),
+            timestamp: Utc::now(),
+            source_ip: ip.to_string(),
+            event_type: "test".to_string(),
+            description: "test".to_string(),
+            severity: severity.to_string(),
+            metadata: serde_json::json!({}),
+        }
+    }
+
+    #[test]
+    fn bans_after_maxretry() {
+        let bl = BanList::new(600_000, 3, 1_000, 60_000);
+        assert!(bl.ingest(&event("1.2.3.4", "low")).is_none());
+        assert!(bl.ingest(&event("1.2.3.4", "low")).is_none());
+        let decision = bl.ingest(&event("1.2.3.4", "low")).expect("should ban on 3rd strike");
+        assert_eq!(decision.ip, "1.2.3.4");
+        assert!(bl.check_ban("1.2.3.4").is_some());
+    }
+
+    #[test]
+    fn allowlist_is_never_banned() {
+        let bl = BanList::new(600_000, 1, 1_000, 60_000).with_allowlist(&["10.0.0.0/8"]);
+        assert!(bl.ingest(&event("10.1.2.3", "critical")).is_none());
+        assert!(bl.check_ban("10.1.2.3").is_none());
+    }
+
+    #[test]
+    fn bantime_escalates_on_repeat_offense() {
+        let bl = BanList::new(600_000, 1, 1_000, 1_000_000);
+        let first = bl.ingest(&event("5.5.5.5", "low")).unwrap();
+        let first_bantime = first.until - current_time_ms();
+
+        // Force the ban to have expired so the next strike re-evaluates.
+        {
+            let mut state = bl.state.lock().unwrap();
+            state.get_mut("5.5.5.5").unwrap().banned_until = Some(0);
+        }
+
+        let second = bl.ingest(&event("5.5.5.5", "low")).unwrap();
+        let second_bantime = second.until - current_time_ms();
+        assert!(second_bantime > first_bantime);
+    }
+
+    #[test]
+    fn cidr_block_matches_prefix() {
+        let block = CidrBlock::from_str("192.168.0.0/16").unwrap();
+        assert!(block.contains(&IpAddr::from_str("192.168.5.9").unwrap()));
+        assert!(!block.contains(&IpAddr::from_str("192.169.0.1").unwrap()));
+    }
+
+    #[test]
+    fn evict_expired_drops_stale_entries() {
+        let bl = BanList::new(100, 10, 1_000, 60_000);
+        bl.ingest(&event("9.9.9.9", "low"));
+        {
+            let mut state = bl.state.lock().unwrap();
+            for ts in state.get_mut("9.9.9.9").unwrap().events.iter_mut() {
+                *ts = 0;
+            }
+        }
+        bl.evict_expired();
+        assert!(bl.state.lock().unwrap().get("9.9.9.9").is_none());
+    }
+}
@@ -0,0 +1,344 @@
+// Attacker IP enforcement via nftables
+// -------------------------------------
+// `banlist.rs` turns repeated offenses into a retry-count-based
+// `BanDecision`, but nothing actually enforces it at the kernel level.
+// This module closes that loop: ThreatEvent severity is weighted
+// (critical=10, high=5, medium=2, low=1) and summed over a decaying
+// sliding window per source IP; once the running score crosses a
+// threshold the IP is installed into a named nftables set via the
+// `nftnl`/`mnl` netlink crates, with a TTL after which the entry is
+// removed automatically. A `dry_run` mode logs the action instead of
+// touching netlink, for deployments without `CAP_NET_ADMIN`.
+
+use crate::ThreatEvent;
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Table/set names the enforcement rules are expected to already exist
+/// in (created out of band, e.g. via an `nft -f` bootstrap script run at
+/// deploy time — this module only manages set membership).
+const NFT_TABLE: &str = "filter";
+const NFT_SET: &str = "apate_banned";
+
+/// Score contribution of one ThreatEvent, by severity. Matches the
+/// severity vocabulary produced by `protocol::analyze_for_threats`.
+fn severity_weight(severity: &str) -> u32 {
+    match severity {
+        "critical" => 10,
+        "high" => 5,
+        "medium" => 2,
+        "low" => 1,
+        _ => 0,
+    }
+}
+
+/// A single enforcement action: `ip` was just added to the nftables set
+/// and will be removed once `expires_at` passes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BanEntry {
+    pub ip: IpAddr,
+    pub expires_at: Instant,
+}
+
+#[derive(Debug, Clone)]
+pub struct BanManagerConfig {
+    /// Sliding window over which severity scores accumulate.
+    pub window: Duration,
+    /// Summed severity score that triggers a ban.
+    pub threshold: u32,
+    /// How long the nftables set membership is kept before expiry.
+    pub ban_ttl: Duration,
+    /// Log-only mode: computes decisions but never touches netlink.
+    pub dry_run: bool,
+}
+
+impl BanManagerConfig {
+    /// 600s window, threshold equivalent to one critical (or two highs, or
+    /// ten lows), 1h ban, dry-run on by default so a fresh deployment
+    /// doesn't start firewalling traffic before an operator opts in.
+    pub fn with_defaults() -> Self {
+        Self {
+            window: Duration::from_secs(600),
+            threshold: 10,
+            ban_ttl: Duration::from_secs(3600),
+            dry_run: true,
+        }
+    }
+}
+
+struct ScoreState {
+    events: VecDeque<(Instant, u32)>,
+}
+
+impl ScoreState {
+    fn new() -> Self {
+        Self { events: VecDeque::new() }
+    }
+}
+
+/// Severity-weighted sliding-window ban enforcer, backed by an nftables set.
+pub struct BanManager {
+    config: BanManagerConfig,
+    scores: Mutex<HashMap<IpAddr, ScoreState>>,
+    banned: Mutex<HashMap<IpAddr, Instant>>,
+}
+
+impl BanManager {
+    pub fn new(config: BanManagerConfig) -> Self {
+        Self {
+            config,
+            scores: Mutex::new(HashMap::new()),
+            banned: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_defaults() -> Self {
+        Self::new(BanManagerConfig::with_defaults())
+    }
+
+    /// Feed a ThreatEvent's severity into its source IP's running score.
+    /// Returns the `BanEntry` if this event pushed the IP over threshold
+    /// and it was (or would be, in dry-run) newly banned.
+    pub fn ingest(&self, event: &ThreatEvent) -> Option<BanEntry> {
+        let ip = IpAddr::from_str(&event.source_ip).ok()?;
+        let weight = severity_weight(&event.severity);
+        if weight == 0 {
+            return None;
+        }
+
+        let now = Instant::now();
+        let total = {
+            let mut scores = self.scores.lock().unwrap();
+            let state = scores.entry(ip).or_insert_with(ScoreState::new);
+
+            state.events.push_back((now, weight));
+            let cutoff = now.checked_sub(self.config.window).unwrap_or(now);
+            while matches!(state.events.front(), Some((ts, _)) if *ts < cutoff) {
+                state.events.pop_front();
+            }
+
+            state.events.iter().map(|(_, w)| *w).sum::<u32>()
+        };
+
+        if total < self.config.threshold {
+            return None;
+        }
+
+        {
+            let mut banned = self.banned.lock().unwrap();
+            if matches!(banned.get(&ip), Some(expires_at) if *expires_at > now) {
+                return None; // already banned, nothing new to install
+            }
+            banned.insert(ip, now + self.config.ban_ttl);
+        }
+        // Give the window a clean slate so immediate re-ingestion right
+        // after a ban doesn't instantly re-trigger once it expires.
+        self.scores.lock().unwrap().remove(&ip);
+
+        let entry = BanEntry { ip, expires_at: now + self.config.ban_ttl };
+        self.install(&entry);
+        Some(entry)
+    }
+
+    fn install(&self, entry: &BanEntry) {
+        if self.config.dry_run {
+            log::info!(
+                "[dry-run] would add {} to nftables set {} {}",
+                entry.ip, NFT_TABLE, NFT_SET
+            );
+            return;
+        }
+
+        if let Err(e) = nft::add_element(entry.ip) {
+            log::error!("failed to install nftables ban for {}: {e}", entry.ip);
+        }
+    }
+
+    fn uninstall(&self, ip: IpAddr) {
+        if self.config.dry_run {
+            log::info!("[dry-run] would remove {ip} from nftables set {NFT_TABLE} {NFT_SET}");
+            return;
+        }
+
+        if let Err(e) = nft::remove_element(ip) {
+            log::error!("failed to remove expired nftables ban for {ip}: {e}");
+        }
+    }
+
+    /// Remove any ban whose TTL has lapsed. Intended to be called
+    /// periodically (e.g. alongside `BanList::evict_expired`).
+    pub fn expire_stale(&self) {
+        let now = Instant::now();
+        let expired: Vec<IpAddr> = {
+            let banned = self.banned.lock().unwrap();
+            banned
+                .iter()
+                .filter(|(_, expires_at)| **expires_at <= now)
+                .map(|(ip, _)| *ip)
+                .collect()
+        };
+
+        for ip in expired {
+            self.banned.lock().unwrap().remove(&ip);
+            self.uninstall(ip);
+        }
+    }
+
+    /// Currently-banned IPs with remaining TTL in seconds, suitable for
+    /// folding into the stats JSON served on port 7879.
+    pub fn banned_snapshot(&self) -> Vec<(String, u64)> {
+        let now = Instant::now();
+        self.banned
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, expires_at)| **expires_at > now)
+            .map(|(ip, expires_at)| (ip.to_string(), (*expires_at - now).as_secs()))
+            .collect()
+    }
+}
+
+/// Netlink plumbing. nftables set management is Linux-specific, so this
+/// is only compiled (and only ever called outside dry-run) on Linux; a
+/// non-Linux build still compiles with dry-run as the only usable mode.
+#[cfg(target_os = "linux")]
+mod nft {
+    use super::{NFT_SET, NFT_TABLE};
+    use nftnl::set::Set;
+    use nftnl::{Batch, FinalizedBatch, MsgType, ProtoFamily, Table};
+    use std::ffi::CString;
+    use std::io;
+    use std::net::IpAddr;
+
+    fn table() -> Table {
+        Table::new(&CString::new(NFT_TABLE).unwrap(), ProtoFamily::Inet)
+    }
+
+    fn batch_for(ip: IpAddr, msg_type: MsgType) -> FinalizedBatch {
+        let table = table();
+        let mut set = Set::new(&CString::new(NFT_SET).unwrap(), 0, &table, ProtoFamily::Inet);
+
+        let mut batch = Batch::new();
+        set.add(&ip);
+        batch.add(&set, msg_type);
+        batch.finalize()
+    }
+
+    fn send_batch(batch: FinalizedBatch) -> io::Result<()> {
+        let socket = mnl::Socket::new(mnl::Bus::Netfilter)?;
+        socket.send_all(&batch)?;
+
+        let portid = socket.portid();
+        let mut buffer = vec![0u8; nftnl::nft_nlmsg_maxsize() as usize];
+        while let Some(message) = socket.recv(&mut buffer)? {
+            match mnl::cb_run(message, 2, portid)? {
+                mnl::CbResult::Stop => break,
+                mnl::CbResult::Ok => continue,
+            }
+        }
+        Ok(())
+    }
+
+    pub fn add_element(ip: IpAddr) -> io::Result<()> {
+        send_batch(batch_for(ip, MsgType::Add))
+    }
+
+    pub fn remove_element(ip: IpAddr) -> io::Result<()> {
+        send_batch(batch_for(ip, MsgType::Del))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod nft {
+    use std::io;
+    use std::net::IpAddr;
+
+    pub fn add_element(_ip: IpAddr) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "nftables set management requires Linux; use dry_run on this platform",
+        ))
+    }
+
+    pub fn remove_element(_ip: IpAddr) -> io::Result<()> {
+        add_element(_ip)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn event(ip: &str, severity: &str) -> ThreatEvent {
+        ThreatEvent {
+            event_id: "test".to_string(),
+            timestamp: Utc::now(),
+            source_ip: ip.to_string(),
+            event_type: "test".to_string(),
+            description: "test".to_string(),
+            severity: severity.to_string(),
+            metadata: serde_json::json!({}),
+        }
+    }
+
+    fn dry_run_config(threshold: u32) -> BanManagerConfig {
+        BanManagerConfig {
+            window: Duration::from_secs(600),
+            threshold,
+            ban_ttl: Duration::from_secs(60),
+            dry_run: true,
+        }
+    }
+
+    #[test]
+    fn low_severity_alone_does_not_cross_threshold() {
+        let mgr = BanManager::new(dry_run_config(10));
+        assert!(mgr.ingest(&event("1.2.3.4", "low")).is_none());
+    }
+
+    #[test]
+    fn accumulated_severity_crosses_threshold() {
+        let mgr = BanManager::new(dry_run_config(10));
+        assert!(mgr.ingest(&event("1.2.3.4", "medium")).is_none()); // 2
+        assert!(mgr.ingest(&event("1.2.3.4", "high")).is_none()); // +5 = 7
+        let entry = mgr.ingest(&event("1.2.3.4", "high")).expect("should ban"); // +5 = 12
+        assert_eq!(entry.ip, IpAddr::from_str("1.2.3.4").unwrap());
+    }
+
+    #[test]
+    fn single_critical_event_bans_immediately() {
+        let mgr = BanManager::new(dry_run_config(10));
+        assert!(mgr.ingest(&event("9.9.9.9", "critical")).is_some());
+    }
+
+    #[test]
+    fn already_banned_ip_does_not_rearm() {
+        let mgr = BanManager::new(dry_run_config(5));
+        assert!(mgr.ingest(&event("5.5.5.5", "critical")).is_some());
+        assert!(mgr.ingest(&event("5.5.5.5", "critical")).is_none());
+    }
+
+    #[test]
+    fn expire_stale_removes_lapsed_bans() {
+        let mgr = BanManager::new(BanManagerConfig {
+            window: Duration::from_secs(600),
+            threshold: 1,
+            ban_ttl: Duration::from_millis(0),
+            dry_run: true,
+        });
+        mgr.ingest(&event("8.8.8.8", "low"));
+        assert_eq!(mgr.banned_snapshot().len(), 0); // already expired by the time we check
+        mgr.expire_stale();
+        assert!(mgr.banned.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn unparseable_ip_is_ignored() {
+        let mgr = BanManager::new(dry_run_config(1));
+        assert!(mgr.ingest(&event("not-an-ip", "critical")).is_none());
+    }
+}
@@ -0,0 +1,256 @@
+// Compact versioned binary codec
+// -------------------------------
+// ThreatEvent/ProtocolMessage are only serializable via serde_json today,
+// which is bulky for sensor-to-sensor transport over UDP. This gives a
+// "beacon" wire format that is cheap to parse and small enough to fit in
+// a single broadcast datagram:
+//
+//   [4-byte magic][1-byte version][u16 len + source_ip][1-byte severity]
+//   [8-byte timestamp_ms][u16 len + event_id][u16 len + event_type]
+//   [u16 len + description][u32 len + metadata json]
+
+use crate::ThreatEvent;
+use chrono::{DateTime, TimeZone, Utc};
+use std::fmt;
+
+const MAGIC: [u8; 4] = *b"APTB";
+const VERSION: u8 = 1;
+
+#[derive(Debug, PartialEq)]
+pub enum DecodeError {
+    TooShort,
+    BadMagic,
+    UnsupportedVersion(u8),
+    Truncated,
+    InvalidUtf8,
+    InvalidSeverity(u8),
+    InvalidMetadata,
+    InvalidTimestamp,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::TooShort => write!(f, "frame shorter than the fixed header"),
+            DecodeError::BadMagic => write!(f, "magic bytes do not match"),
+            DecodeError::UnsupportedVersion(v) => write!(f, "unsupported beacon version {}", v),
+            DecodeError::Truncated => write!(f, "length prefix overruns the remaining frame"),
+            DecodeError::InvalidUtf8 => write!(f, "field is not valid utf-8"),
+            DecodeError::InvalidSeverity(b) => write!(f, "unknown severity byte {}", b),
+            DecodeError::InvalidMetadata => write!(f, "metadata field is not valid json"),
+            DecodeError::InvalidTimestamp => write!(f, "timestamp_ms does not map to a valid instant"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+fn severity_to_byte(severity: &str) -> u8 {
+    match severity {
+        "low" => 0,
+        "medium" => 1,
+        "high" => 2,
+        "critical" => 3,
+        _ => 0,
+    }
+}
+
+fn byte_to_severity(byte: u8) -> Result<&'static str, DecodeError> {
+    match byte {
+        0 => Ok("low"),
+        1 => Ok("medium"),
+        2 => Ok("high"),
+        3 => Ok("critical"),
+        other => Err(DecodeError::InvalidSeverity(other)),
+    }
+}
+
+/// Bounds-checked cursor over an encode buffer so every read validates its
+/// length prefix before slicing.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        let end = self.pos.checked_add(len).ok_or(DecodeError::Truncated)?;
+        if end > self.data.len() {
+            return Err(DecodeError::Truncated);
+        }
+        let slice = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn take_u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn take_u16(&mut self) -> Result<u16, DecodeError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn take_u32(&mut self) -> Result<u32, DecodeError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn take_u64(&mut self) -> Result<u64, DecodeError> {
+        let bytes = self.take(8)?;
+        Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn take_str16(&mut self) -> Result<String, DecodeError> {
+        let len = self.take_u16()? as usize;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::InvalidUtf8)
+    }
+}
+
+fn write_str16(buf: &mut Vec<u8>, s: &str) {
+    let bytes = s.as_bytes();
+    let len = bytes.len().min(u16::MAX as usize) as u16;
+    buf.extend_from_slice(&len.to_le_bytes());
+    buf.extend_from_slice(&bytes[..len as usize]);
+}
+
+/// Encode a ThreatEvent into the compact beacon wire format.
+pub fn encode_beacon(event: &ThreatEvent) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(64);
+    buf.extend_from_slice(&MAGIC);
+    buf.push(VERSION);
+    write_str16(&mut buf, &event.source_ip);
+    buf.push(severity_to_byte(&event.severity));
+    buf.extend_from_slice(&(event.timestamp.timestamp_millis() as u64).to_le_bytes());
+    write_str16(&mut buf, &event.event_id);
+    write_str16(&mut buf, &event.event_type);
+    write_str16(&mut buf, &event.description);
+
+    let metadata = serde_json::to_vec(&event.metadata).unwrap_or_default();
+    let metadata_len = metadata.len().min(u32::MAX as usize) as u32;
+    buf.extend_from_slice(&metadata_len.to_le_bytes());
+    buf.extend_from_slice(&metadata[..metadata_len as usize]);
+
+    buf
+}
+
+/// Decode a beacon frame into a ThreatEvent, bounds-checking every length
+/// prefix before slicing so truncated input returns an error, never a panic.
+pub fn decode_beacon(data: &[u8]) -> Result<ThreatEvent, DecodeError> {
+    if data.len() < MAGIC.len() + 1 {
+        return Err(DecodeError::TooShort);
+    }
+
+    let mut reader = Reader::new(data);
+    let magic = reader.take(4)?;
+    if magic != MAGIC {
+        return Err(DecodeError::BadMagic);
+    }
+
+    let version = reader.take_u8()?;
+    if version != VERSION {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+
+    let source_ip = reader.take_str16()?;
+    let severity = byte_to_severity(reader.take_u8()?)?.to_string();
+    let timestamp_ms = reader.take_u64()?;
+    let timestamp = Utc
+        .timestamp_millis_opt(timestamp_ms as i64)
+        .single()
+        .ok_or(DecodeError::InvalidTimestamp)?;
+    let event_id = reader.take_str16()?;
+    let event_type = reader.take_str16()?;
+    let description = reader.take_str16()?;
+
+    let metadata_len = reader.take_u32()? as usize;
+    let metadata_bytes = reader.take(metadata_len)?;
+    let metadata: serde_json::Value =
+        serde_json::from_slice(metadata_bytes).map_err(|_| DecodeError::InvalidMetadata)?;
+
+    Ok(ThreatEvent {
+        event_id,
+        timestamp,
+        source_ip,
+        event_type,
+        description,
+        severity,
+        metadata,
+    })
+}
+
+/// Convenience for callers that only have a millisecond timestamp handy.
+pub fn timestamp_from_millis(ms: u64) -> Option<DateTime<Utc>> {
+    Utc.timestamp_millis_opt(ms as i64).single()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_event() -> ThreatEvent {
+        ThreatEvent {
+            event_id: "evt-1".to_string(),
+            timestamp: Utc::now(),
+            source_ip: "203.0.113.7".to_string(),
+            event_type: "sql_injection".to_string(),
+            description: "SQL injection attempt detected".to_string(),
+            severity: "high".to_string(),
+            metadata: serde_json::json!({"message_id": "m-1", "threat_count": 2}),
+        }
+    }
+
+    #[test]
+    fn round_trips_losslessly() {
+        let event = sample_event();
+        let encoded = encode_beacon(&event);
+        let decoded = decode_beacon(&encoded).expect("valid frame decodes");
+
+        assert_eq!(decoded.event_id, event.event_id);
+        assert_eq!(decoded.source_ip, event.source_ip);
+        assert_eq!(decoded.event_type, event.event_type);
+        assert_eq!(decoded.description, event.description);
+        assert_eq!(decoded.severity, event.severity);
+        assert_eq!(decoded.metadata, event.metadata);
+        assert_eq!(decoded.timestamp.timestamp_millis(), event.timestamp.timestamp_millis());
+    }
+
+    #[test]
+    fn rejects_bad_magic() {
+        let mut encoded = encode_beacon(&sample_event());
+        encoded[0] = b'X';
+        assert_eq!(decode_beacon(&encoded), Err(DecodeError::BadMagic));
+    }
+
+    #[test]
+    fn rejects_unknown_version() {
+        let mut encoded = encode_beacon(&sample_event());
+        encoded[4] = 99;
+        assert_eq!(decode_beacon(&encoded), Err(DecodeError::UnsupportedVersion(99)));
+    }
+
+    #[test]
+    fn rejects_truncated_frames_without_panicking() {
+        let encoded = encode_beacon(&sample_event());
+        for cut in 0..encoded.len() {
+            let _ = decode_beacon(&encoded[..cut]);
+        }
+        assert_eq!(decode_beacon(&encoded[..6]), Err(DecodeError::Truncated));
+    }
+
+    #[test]
+    fn rejects_invalid_severity_byte() {
+        let mut encoded = encode_beacon(&sample_event());
+        // source_ip is 11 bytes, so the severity byte sits right after the
+        // magic+version+u16-len+source_ip header.
+        let severity_idx = 4 + 1 + 2 + "203.0.113.7".len();
+        encoded[severity_idx] = 42;
+        assert_eq!(decode_beacon(&encoded), Err(DecodeError::InvalidSeverity(42)));
+    }
+}
@@ -0,0 +1,361 @@
+// Typed config file + interactive wizard
+// ----------------------------------------
+// Bind addresses, TTL, jitter bounds, emulated banners, and the
+// suspicious-pattern list were all baked into main.rs/protocol.rs as
+// literals, so customizing a deployment meant recompiling. This loads
+// them from a TOML file into a typed `Config`, and `--wizard` writes one
+// out interactively so an operator can stand up a customized honeypot
+// without touching the source.
+
+use crate::protocol::CompiledRule;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Serialize(toml::ser::Error),
+    InvalidRegex { rule_name: String, source: regex::Error },
+    InvalidListenAddr { field: &'static str, value: String, source: std::net::AddrParseError },
+    InvalidJitter { min_ms: u64, max_ms: u64 },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "config I/O error: {e}"),
+            ConfigError::Parse(e) => write!(f, "malformed config TOML: {e}"),
+            ConfigError::Serialize(e) => write!(f, "failed to serialize config: {e}"),
+            ConfigError::InvalidRegex { rule_name, source } => {
+                write!(f, "detection rule '{rule_name}' has an invalid pattern: {source}")
+            }
+            ConfigError::InvalidListenAddr { field, value, source } => {
+                write!(f, "{field} = {value:?} is not a valid listen address: {source}")
+            }
+            ConfigError::InvalidJitter { min_ms, max_ms } => {
+                write!(f, "jitter.min_ms ({min_ms}) must be <= jitter.max_ms ({max_ms})")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<io::Error> for ConfigError {
+    fn from(e: io::Error) -> Self {
+        ConfigError::Io(e)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListenConfig {
+    pub echo: String,
+    pub stats: String,
+}
+
+/// Emulated service banners per protocol, sent in place of the
+/// hardcoded literals `process_data` used to return.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BannerConfig {
+    pub ssh: String,
+    pub http: String,
+    pub ftp: String,
+    pub smtp: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JitterConfig {
+    pub min_ms: u64,
+    pub max_ms: u64,
+}
+
+/// One user-supplied detection rule, compiled via `compile_detection_rules`
+/// before it's merged into `analyze_for_threats`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DetectionRule {
+    pub name: String,
+    pub pattern: String,
+    pub severity: String,
+}
+
+/// Tuning for the `gossip` subsystem that shares observed-IP digests
+/// between Apate deployments. An empty `bind` disables it entirely, so
+/// existing deployments that don't list this section keep running as a
+/// standalone node.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GossipConfig {
+    pub bind: String,
+    /// Public addresses for nodes behind NAT that can't auto-detect their
+    /// own endpoint. Handed to `GossipNode`, which stores them; gossip
+    /// digests do not carry them to peers.
+    pub advertise_addresses: Vec<String>,
+    /// Peers to start gossiping with immediately; more are learned from
+    /// the addresses peers report back.
+    pub seed_peers: Vec<String>,
+    pub digest_interval_ms: u64,
+}
+
+impl Default for GossipConfig {
+    fn default() -> Self {
+        Self {
+            bind: String::new(),
+            advertise_addresses: Vec::new(),
+            seed_peers: Vec::new(),
+            digest_interval_ms: 5_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub listen: ListenConfig,
+    pub banners: BannerConfig,
+    pub jitter: JitterConfig,
+    #[serde(default)]
+    pub detection: Vec<DetectionRule>,
+    #[serde(default)]
+    pub gossip: GossipConfig,
+}
+
+impl Config {
+    /// Matches the literals the server used before this module existed.
+    pub fn with_defaults() -> Self {
+        Self {
+            listen: ListenConfig {
+                echo: "0.0.0.0:7878".to_string(),
+                stats: "0.0.0.0:7879".to_string(),
+            },
+            banners: BannerConfig {
+                ssh: "SSH-2.0-OpenSSH_8.9p1".to_string(),
+                http: "HTTP/1.1".to_string(),
+                ftp: "220 FTP Server (vsftpd 3.0.3) ready.".to_string(),
+                smtp: "220 mail.example.com ESMTP Postfix".to_string(),
+            },
+            jitter: JitterConfig { min_ms: 1, max_ms: 5 },
+            detection: Vec::new(),
+            gossip: GossipConfig::default(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let raw = std::fs::read_to_string(path)?;
+        toml::from_str(&raw).map_err(ConfigError::Parse)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        let raw = toml::to_string_pretty(self).map_err(ConfigError::Serialize)?;
+        std::fs::write(path, raw)?;
+        Ok(())
+    }
+
+    /// Validate listen addresses and jitter bounds, failing fast with a
+    /// `ConfigError` instead of panicking deep in `main`/`handle_client`
+    /// on operator-supplied (or wizard-edited) values.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        self.listen.echo.parse::<SocketAddr>().map_err(|source| ConfigError::InvalidListenAddr {
+            field: "listen.echo",
+            value: self.listen.echo.clone(),
+            source,
+        })?;
+        self.listen.stats.parse::<SocketAddr>().map_err(|source| ConfigError::InvalidListenAddr {
+            field: "listen.stats",
+            value: self.listen.stats.clone(),
+            source,
+        })?;
+
+        if self.jitter.min_ms > self.jitter.max_ms {
+            return Err(ConfigError::InvalidJitter {
+                min_ms: self.jitter.min_ms,
+                max_ms: self.jitter.max_ms,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Compile every user-supplied detection rule, failing on the first
+    /// bad pattern instead of letting it silently never match at runtime.
+    pub fn compile_detection_rules(&self) -> Result<Vec<CompiledRule>, ConfigError> {
+        self.detection
+            .iter()
+            .map(|rule| {
+                Regex::new(&rule.pattern)
+                    .map(|regex| CompiledRule {
+                        name: rule.name.clone(),
+                        severity: rule.severity.clone(),
+                        regex,
+                    })
+                    .map_err(|source| ConfigError::InvalidRegex {
+                        rule_name: rule.name.clone(),
+                        source,
+                    })
+            })
+            .collect()
+    }
+}
+
+fn prompt(label: &str, default: &str) -> String {
+    print!("{label} [{default}]: ");
+    let _ = io::stdout().flush();
+
+    let mut line = String::new();
+    if io::stdin().read_line(&mut line).is_err() {
+        return default.to_string();
+    }
+    let trimmed = line.trim();
+    if trimmed.is_empty() {
+        default.to_string()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+/// Prompt for a comma-separated list, e.g. seed peers or advertise
+/// addresses. A blank answer means "none".
+fn prompt_list(label: &str, default: &[String]) -> Vec<String> {
+    let default_joined = default.join(",");
+    let answer = prompt(&format!("{label} (comma-separated)"), &default_joined);
+    answer
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Interactively prompt for every tunable, starting from
+/// `Config::with_defaults()`. Detection rules are added one at a time
+/// until the operator enters a blank name.
+pub fn run_wizard() -> Config {
+    let defaults = Config::with_defaults();
+    println!("Apate honeypot configuration wizard — press Enter to accept the default.");
+
+    let mut config = Config {
+        listen: ListenConfig {
+            echo: prompt("Echo listen address", &defaults.listen.echo),
+            stats: prompt("Stats listen address", &defaults.listen.stats),
+        },
+        banners: BannerConfig {
+            ssh: prompt("Emulated SSH banner", &defaults.banners.ssh),
+            http: prompt("Emulated HTTP version", &defaults.banners.http),
+            ftp: prompt("Emulated FTP banner", &defaults.banners.ftp),
+            smtp: prompt("Emulated SMTP banner", &defaults.banners.smtp),
+        },
+        jitter: JitterConfig {
+            min_ms: prompt("Jitter min (ms)", &defaults.jitter.min_ms.to_string())
+                .parse()
+                .unwrap_or(defaults.jitter.min_ms),
+            max_ms: prompt("Jitter max (ms)", &defaults.jitter.max_ms.to_string())
+                .parse()
+                .unwrap_or(defaults.jitter.max_ms),
+        },
+        detection: Vec::new(),
+        gossip: GossipConfig {
+            bind: prompt("Gossip bind address (blank disables gossip)", &defaults.gossip.bind),
+            advertise_addresses: prompt_list("Gossip advertise addresses", &defaults.gossip.advertise_addresses),
+            seed_peers: prompt_list("Gossip seed peers", &defaults.gossip.seed_peers),
+            digest_interval_ms: prompt("Gossip digest interval (ms)", &defaults.gossip.digest_interval_ms.to_string())
+                .parse()
+                .unwrap_or(defaults.gossip.digest_interval_ms),
+        },
+    };
+
+    println!("Add custom detection rules (blank name to stop):");
+    loop {
+        let name = prompt("  rule name", "");
+        if name.is_empty() {
+            break;
+        }
+        let pattern = prompt("  regex pattern", "");
+        let severity = prompt("  severity (low/medium/high/critical)", "medium");
+        config.detection.push(DetectionRule { name, pattern, severity });
+    }
+
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_round_trip_through_toml() {
+        let config = Config::with_defaults();
+        let raw = toml::to_string_pretty(&config).unwrap();
+        let parsed: Config = toml::from_str(&raw).unwrap();
+        assert_eq!(parsed.listen.echo, config.listen.echo);
+        assert_eq!(parsed.banners.ssh, config.banners.ssh);
+    }
+
+    #[test]
+    fn compile_detection_rules_accepts_valid_patterns() {
+        let mut config = Config::with_defaults();
+        config.detection.push(DetectionRule {
+            name: "custom_probe".to_string(),
+            pattern: r"(?i)nikto".to_string(),
+            severity: "medium".to_string(),
+        });
+        let compiled = config.compile_detection_rules().unwrap();
+        assert_eq!(compiled.len(), 1);
+        assert!(compiled[0].regex.is_match("Nikto/2.5.0"));
+    }
+
+    #[test]
+    fn compile_detection_rules_fails_fast_on_bad_pattern() {
+        let mut config = Config::with_defaults();
+        config.detection.push(DetectionRule {
+            name: "broken".to_string(),
+            pattern: "(unclosed".to_string(),
+            severity: "low".to_string(),
+        });
+        let err = config.compile_detection_rules().unwrap_err();
+        assert!(matches!(err, ConfigError::InvalidRegex { rule_name, .. } if rule_name == "broken"));
+    }
+
+    #[test]
+    fn validate_rejects_malformed_listen_addr() {
+        let mut config = Config::with_defaults();
+        config.listen.echo = "not-an-address".to_string();
+        let err = config.validate().unwrap_err();
+        assert!(matches!(err, ConfigError::InvalidListenAddr { field, .. } if field == "listen.echo"));
+    }
+
+    #[test]
+    fn validate_rejects_inverted_jitter_bounds() {
+        let mut config = Config::with_defaults();
+        config.jitter = JitterConfig { min_ms: 10, max_ms: 1 };
+        let err = config.validate().unwrap_err();
+        assert!(matches!(err, ConfigError::InvalidJitter { min_ms: 10, max_ms: 1 }));
+    }
+
+    #[test]
+    fn validate_accepts_defaults() {
+        assert!(Config::with_defaults().validate().is_ok());
+    }
+
+    #[test]
+    fn missing_detection_key_defaults_to_empty() {
+        let raw = r#"
+            [listen]
+            echo = "0.0.0.0:7878"
+            stats = "0.0.0.0:7879"
+
+            [banners]
+            ssh = "SSH-2.0-OpenSSH_8.9p1"
+            http = "HTTP/1.1"
+            ftp = "220 ready"
+            smtp = "220 ready"
+
+            [jitter]
+            min_ms = 1
+            max_ms = 5
+        "#;
+        let config: Config = toml::from_str(raw).unwrap();
+        assert!(config.detection.is_empty());
+    }
+}
@@ -0,0 +1,284 @@
+// Authenticated encryption envelope for ProtocolMessage
+// -------------------------------------------------------
+// ProtocolMessage.payload travels in plaintext today, so any node-to-node
+// transport of captured attacker data is unprotected. This wraps a
+// message in a ChaCha20-Poly1305 AEAD envelope: the frame header (id,
+// timestamp, source) stays in the clear but is bound as associated data
+// so it can't be swapped onto a different ciphertext, while message_type,
+// payload, and fingerprint are sealed.
+
+use crate::{MessageType, ProtocolMessage};
+use chacha20poly1305::aead::{Aead, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use chrono::{TimeZone, Utc};
+use rand::RngCore;
+use std::fmt;
+use std::net::SocketAddr;
+use std::str::FromStr;
+
+const MAGIC: [u8; 4] = *b"APTC";
+const NONCE_LEN: usize = 12;
+
+#[derive(Debug, PartialEq)]
+pub enum CryptoError {
+    TooShort,
+    BadMagic,
+    Truncated,
+    InvalidUtf8,
+    InvalidTimestamp,
+    InvalidSource,
+    InvalidBody,
+    DecryptFailed,
+}
+
+impl fmt::Display for CryptoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CryptoError::TooShort => write!(f, "frame shorter than the fixed header"),
+            CryptoError::BadMagic => write!(f, "magic bytes do not match"),
+            CryptoError::Truncated => write!(f, "length prefix overruns the remaining frame"),
+            CryptoError::InvalidUtf8 => write!(f, "header field is not valid utf-8"),
+            CryptoError::InvalidTimestamp => write!(f, "timestamp_ms does not map to a valid instant"),
+            CryptoError::InvalidSource => write!(f, "source field is not a valid socket address"),
+            CryptoError::InvalidBody => write!(f, "decrypted body is not valid json"),
+            CryptoError::DecryptFailed => write!(f, "AEAD open failed (wrong key, tampered frame, or swapped header)"),
+        }
+    }
+}
+
+impl std::error::Error for CryptoError {}
+
+/// The fields that are sealed; the header (id/timestamp/source) is bound
+/// as associated data instead of being re-encrypted.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SealedBody {
+    message_type: MessageType,
+    protocol_version: Option<String>,
+    payload: String,
+    fingerprint: Option<String>,
+}
+
+fn build_aad(id: &str, timestamp_ms: u64, source: &str) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(id.len() + source.len() + 16);
+    aad.extend_from_slice(&(id.len() as u16).to_le_bytes());
+    aad.extend_from_slice(id.as_bytes());
+    aad.extend_from_slice(&timestamp_ms.to_le_bytes());
+    aad.extend_from_slice(&(source.len() as u16).to_le_bytes());
+    aad.extend_from_slice(source.as_bytes());
+    aad
+}
+
+/// Seal a ProtocolMessage with a 32-byte ChaCha20-Poly1305 key. The header
+/// (id, timestamp, source) is stored in cleartext but bound as associated
+/// data; everything else is encrypted.
+pub fn seal(msg: &ProtocolMessage, key: &[u8; 32]) -> Vec<u8> {
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+
+    let mut nonce_bytes = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce_bytes);
+    let nonce = Nonce::from_slice(&nonce_bytes);
+
+    let id = msg.id.as_str();
+    let source = msg.source.to_string();
+    let timestamp_ms = msg.timestamp.timestamp_millis() as u64;
+    let aad = build_aad(id, timestamp_ms, &source);
+
+    let body = SealedBody {
+        message_type: msg.message_type,
+        protocol_version: msg.protocol_version.clone(),
+        payload: msg.payload.clone(),
+        fingerprint: msg.fingerprint.clone(),
+    };
+    let plaintext = serde_json::to_vec(&body).unwrap_or_default();
+
+    let ciphertext = cipher
+        .encrypt(nonce, chacha20poly1305::aead::Payload { msg: &plaintext, aad: &aad })
+        .expect("chacha20poly1305 encryption is infallible for valid key/nonce sizes");
+
+    let mut frame = Vec::with_capacity(4 + NONCE_LEN + aad.len() + 4 + ciphertext.len());
+    frame.extend_from_slice(&MAGIC);
+    frame.extend_from_slice(&nonce_bytes);
+    frame.extend_from_slice(&aad);
+    frame.extend_from_slice(&(ciphertext.len() as u32).to_le_bytes());
+    frame.extend_from_slice(&ciphertext);
+    frame
+}
+
+/// Open a sealed frame with a single 32-byte key. Returns `DecryptFailed`
+/// on any authentication failure, never panicking on malformed input.
+pub fn open(frame: &[u8], key: &[u8; 32]) -> Result<ProtocolMessage, CryptoError> {
+    if frame.len() < MAGIC.len() + NONCE_LEN {
+        return Err(CryptoError::TooShort);
+    }
+    if &frame[..4] != &MAGIC {
+        return Err(CryptoError::BadMagic);
+    }
+
+    let nonce_bytes = &frame[4..4 + NONCE_LEN];
+    let mut pos = 4 + NONCE_LEN;
+
+    let id_len = take_u16(frame, &mut pos)? as usize;
+    let id_bytes = take(frame, &mut pos, id_len)?;
+    let id = String::from_utf8(id_bytes.to_vec()).map_err(|_| CryptoError::InvalidUtf8)?;
+
+    let timestamp_ms = take_u64(frame, &mut pos)?;
+    let timestamp = Utc
+        .timestamp_millis_opt(timestamp_ms as i64)
+        .single()
+        .ok_or(CryptoError::InvalidTimestamp)?;
+
+    let source_len = take_u16(frame, &mut pos)? as usize;
+    let source_bytes = take(frame, &mut pos, source_len)?;
+    let source_str = std::str::from_utf8(source_bytes).map_err(|_| CryptoError::InvalidUtf8)?;
+    let source = SocketAddr::from_str(source_str).map_err(|_| CryptoError::InvalidSource)?;
+
+    let aad_end = pos;
+    let aad = &frame[4 + NONCE_LEN..aad_end];
+
+    let ciphertext_len = take_u32(frame, &mut pos)? as usize;
+    let ciphertext = take(frame, &mut pos, ciphertext_len)?;
+
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    let nonce = Nonce::from_slice(nonce_bytes);
+    let plaintext = cipher
+        .decrypt(nonce, chacha20poly1305::aead::Payload { msg: ciphertext, aad })
+        .map_err(|_| CryptoError::DecryptFailed)?;
+
+    let body: SealedBody = serde_json::from_slice(&plaintext).map_err(|_| CryptoError::InvalidBody)?;
+
+    Ok(ProtocolMessage {
+        id,
+        timestamp,
+        source,
+        message_type: body.message_type,
+        protocol_version: body.protocol_version,
+        payload: body.payload,
+        fingerprint: body.fingerprint,
+    })
+}
+
+fn take<'a>(data: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8], CryptoError> {
+    let end = pos.checked_add(len).ok_or(CryptoError::Truncated)?;
+    if end > data.len() {
+        return Err(CryptoError::Truncated);
+    }
+    let slice = &data[*pos..end];
+    *pos = end;
+    Ok(slice)
+}
+
+fn take_u16(data: &[u8], pos: &mut usize) -> Result<u16, CryptoError> {
+    let bytes = take(data, pos, 2)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn take_u32(data: &[u8], pos: &mut usize) -> Result<u32, CryptoError> {
+    let bytes = take(data, pos, 4)?;
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn take_u64(data: &[u8], pos: &mut usize) -> Result<u64, CryptoError> {
+    let bytes = take(data, pos, 8)?;
+    Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// Holds the current signing/sealing key plus the previous one, so a key
+/// rotation doesn't break in-flight messages: open tries `current` first
+/// and falls back to `previous`.
+pub struct KeyRing {
+    current: [u8; 32],
+    previous: Option<[u8; 32]>,
+}
+
+impl KeyRing {
+    pub fn new(current: [u8; 32]) -> Self {
+        Self { current, previous: None }
+    }
+
+    pub fn rotate(&mut self, new_key: [u8; 32]) {
+        self.previous = Some(self.current);
+        self.current = new_key;
+    }
+
+    pub fn seal(&self, msg: &ProtocolMessage) -> Vec<u8> {
+        seal(msg, &self.current)
+    }
+
+    pub fn open(&self, frame: &[u8]) -> Result<ProtocolMessage, CryptoError> {
+        match open(frame, &self.current) {
+            Ok(msg) => Ok(msg),
+            Err(_) => match self.previous {
+                Some(prev) => open(frame, &prev),
+                None => open(frame, &self.current),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn sample_message() -> ProtocolMessage {
+        ProtocolMessage {
+            id: "msg-1".to_string(),
+            timestamp: Utc::now(),
+            source: "127.0.0.1:4444".parse().unwrap(),
+            message_type: MessageType::SshHandshake,
+            protocol_version: Some("2.0".to_string()),
+            payload: "SSH-2.0-libssh".to_string(),
+            fingerprint: Some("abc123".to_string()),
+        }
+    }
+
+    #[test]
+    fn round_trips_with_same_key() {
+        let key = [7u8; 32];
+        let msg = sample_message();
+        let sealed = seal(&msg, &key);
+        let opened = open(&sealed, &key).expect("should decrypt with matching key");
+
+        assert_eq!(opened.id, msg.id);
+        assert_eq!(opened.payload, msg.payload);
+        assert_eq!(opened.message_type, msg.message_type);
+        assert_eq!(opened.protocol_version, msg.protocol_version);
+        assert_eq!(opened.fingerprint, msg.fingerprint);
+    }
+
+    #[test]
+    fn wrong_key_fails_to_open() {
+        let sealed = seal(&sample_message(), &[1u8; 32]);
+        assert_eq!(open(&sealed, &[2u8; 32]), Err(CryptoError::DecryptFailed));
+    }
+
+    #[test]
+    fn tampered_header_is_rejected() {
+        let key = [3u8; 32];
+        let mut sealed = seal(&sample_message(), &key);
+        // Flip a bit inside the AAD-bound id field, keeping it valid UTF-8
+        // so the frame parses and the AEAD check is what rejects it.
+        let tamper_idx = 4 + NONCE_LEN + 2;
+        sealed[tamper_idx] ^= 0x01;
+        assert_eq!(open(&sealed, &key), Err(CryptoError::DecryptFailed));
+    }
+
+    #[test]
+    fn key_ring_falls_back_to_previous_key() {
+        let old_key = [9u8; 32];
+        let mut ring = KeyRing::new(old_key);
+        let sealed = ring.seal(&sample_message());
+
+        ring.rotate([10u8; 32]);
+        let opened = ring.open(&sealed).expect("should fall back to previous key");
+        assert_eq!(opened.id, "msg-1");
+    }
+
+    #[test]
+    fn truncated_frame_does_not_panic() {
+        let sealed = seal(&sample_message(), &[4u8; 32]);
+        for cut in 0..sealed.len() {
+            let _ = open(&sealed[..cut], &[4u8; 32]);
+        }
+    }
+}
@@ -0,0 +1,356 @@
+// Stateful fake SSH shell emulation
+// ----------------------------------
+// A bare one-line SSH banner convinces nobody that's poking past the
+// handshake; this gives an attacker a consistent-looking shell instead:
+// a small in-memory filesystem seeded with `/etc/passwd`, a home
+// directory, and `.bashrc`, plus interpreters for the handful of
+// commands attackers reach for first. State lives for the life of one
+// connection, so `mkdir foo`, then `echo hi >> foo/bar`, then `cat foo/bar`
+// behaves the way a real shell would. Each line is one command; `&&` and
+// `;` chaining are not interpreted. Every line is still run through
+// `analyze_for_threats` for classification before it's "executed".
+
+use crate::protocol::analyze_for_threats;
+use crate::{MessageType, ProtocolMessage, ThreatEvent};
+use chrono::Utc;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use uuid::Uuid;
+
+const HOSTNAME: &str = "prod-web-03";
+const USERNAME: &str = "deploy";
+const UID: u32 = 1000;
+const GID: u32 = 1000;
+
+#[derive(Debug, Clone)]
+enum FileNode {
+    File(String),
+    Dir,
+}
+
+fn default_passwd() -> String {
+    format!(
+        "root:x:0:0:root:/root:/bin/bash\n\
+         daemon:x:1:1:daemon:/usr/sbin:/usr/sbin/nologin\n\
+         {USERNAME}:x:{UID}:{GID}:{USERNAME}:/home/{USERNAME}:/bin/bash\n"
+    )
+}
+
+fn default_bashrc() -> String {
+    "# ~/.bashrc\nexport PATH=$PATH:/usr/local/bin\nalias ll='ls -la'\n".to_string()
+}
+
+/// Per-connection fake shell: a filesystem, a working directory, and the
+/// identity it presents as.
+pub struct ShellSession {
+    cwd: String,
+    filesystem: HashMap<String, FileNode>,
+    peer_addr: SocketAddr,
+}
+
+impl ShellSession {
+    pub fn new(peer_addr: SocketAddr) -> Self {
+        let home = format!("/home/{USERNAME}");
+        let mut filesystem = HashMap::new();
+        filesystem.insert("/".to_string(), FileNode::Dir);
+        filesystem.insert("/etc".to_string(), FileNode::Dir);
+        filesystem.insert("/etc/passwd".to_string(), FileNode::File(default_passwd()));
+        filesystem.insert("/etc/hostname".to_string(), FileNode::File(format!("{HOSTNAME}\n")));
+        filesystem.insert("/home".to_string(), FileNode::Dir);
+        filesystem.insert(home.clone(), FileNode::Dir);
+        filesystem.insert(format!("{home}/.bashrc"), FileNode::File(default_bashrc()));
+
+        Self {
+            cwd: home,
+            filesystem,
+            peer_addr,
+        }
+    }
+
+    /// Classify the raw line as a threat, interpret it against the fake
+    /// filesystem, and return the rendered shell output (ending in the
+    /// next prompt) plus any `ThreatEvent` the line tripped.
+    pub fn execute(&mut self, line: &str) -> (String, Option<ThreatEvent>) {
+        let line = line.trim();
+
+        let message = ProtocolMessage {
+            id: Uuid::new_v4().to_string(),
+            timestamp: Utc::now(),
+            source: self.peer_addr,
+            message_type: MessageType::SshHandshake,
+            protocol_version: None,
+            payload: line.to_string(),
+            fingerprint: None,
+        };
+        let threat = analyze_for_threats(&message);
+
+        let body = self.interpret(line);
+        let output = format!("{body}{}", self.prompt());
+        (output, threat)
+    }
+
+    pub fn prompt(&self) -> String {
+        format!("{USERNAME}@{HOSTNAME}:{}$ ", self.cwd)
+    }
+
+    /// Resolve `path` (absolute or relative to `cwd`) into a normalized
+    /// absolute path, collapsing `.` and `..` components without ever
+    /// touching the real filesystem.
+    fn resolve(&self, path: &str) -> String {
+        let base: Vec<&str> = if path.starts_with('/') {
+            Vec::new()
+        } else {
+            self.cwd.split('/').filter(|s| !s.is_empty()).collect()
+        };
+        let mut parts = base;
+
+        for component in path.split('/') {
+            match component {
+                "" | "." => {}
+                ".." => {
+                    parts.pop();
+                }
+                other => parts.push(other),
+            }
+        }
+
+        if parts.is_empty() {
+            "/".to_string()
+        } else {
+            format!("/{}", parts.join("/"))
+        }
+    }
+
+    fn interpret(&mut self, line: &str) -> String {
+        if line.is_empty() {
+            return String::new();
+        }
+
+        // `echo TEXT >> FILE` / `echo TEXT > FILE` redirection, the
+        // shorthand attackers reach for to drop a marker file or tamper
+        // with `.bashrc`.
+        if let Some(rest) = line.strip_prefix("echo ") {
+            if let Some((text, target, append)) = split_redirection(rest) {
+                return self.write_file(&target, &text, append);
+            }
+            return format!("{text}\n", text = unquote(rest));
+        }
+
+        let mut parts = line.split_whitespace();
+        let cmd = parts.next().unwrap_or("");
+        let args: Vec<&str> = parts.collect();
+
+        match cmd {
+            "pwd" => format!("{}\n", self.cwd),
+            "whoami" => format!("{USERNAME}\n"),
+            "id" => format!(
+                "uid={UID}({USERNAME}) gid={GID}({USERNAME}) groups={GID}({USERNAME})\n"
+            ),
+            "uname" => {
+                if args.iter().any(|a| *a == "-a") {
+                    format!("Linux {HOSTNAME} 5.15.0-91-generic #101-Ubuntu SMP x86_64 GNU/Linux\n")
+                } else {
+                    "Linux\n".to_string()
+                }
+            }
+            "hostname" => format!("{HOSTNAME}\n"),
+            "ls" => self.ls(args.first().copied()),
+            "cd" => self.cd(args.first().copied().unwrap_or("/home/deploy")),
+            "cat" => self.cat(args.first().copied()),
+            "mkdir" => self.mkdir(args.first().copied()),
+            "ps" => "  PID TTY          TIME CMD\n    1 ?        00:00:01 sshd\n   42 pts/0    00:00:00 bash\n   87 pts/0    00:00:00 ps\n".to_string(),
+            "wget" | "curl" => self.fetch(cmd, args.first().copied()),
+            _ => format!("bash: {cmd}: command not found\n"),
+        }
+    }
+
+    fn ls(&self, path: Option<&str>) -> String {
+        let target = self.resolve(path.unwrap_or("."));
+        if !matches!(self.filesystem.get(&target), Some(FileNode::Dir)) {
+            return format!("ls: cannot access '{}': No such file or directory\n", path.unwrap_or("."));
+        }
+
+        let prefix = if target == "/" { "/".to_string() } else { format!("{target}/") };
+        let mut entries: Vec<&str> = self
+            .filesystem
+            .keys()
+            .filter_map(|p| {
+                p.strip_prefix(&prefix)
+                    .filter(|rest| !rest.is_empty() && !rest.contains('/'))
+            })
+            .collect();
+        entries.sort_unstable();
+        entries.join("  ") + if entries.is_empty() { "" } else { "\n" }
+    }
+
+    fn cd(&mut self, path: &str) -> String {
+        let target = self.resolve(path);
+        match self.filesystem.get(&target) {
+            Some(FileNode::Dir) => {
+                self.cwd = target;
+                String::new()
+            }
+            Some(FileNode::File(_)) => format!("bash: cd: {path}: Not a directory\n"),
+            None => format!("bash: cd: {path}: No such file or directory\n"),
+        }
+    }
+
+    fn cat(&self, path: Option<&str>) -> String {
+        let Some(path) = path else {
+            return String::new();
+        };
+        let target = self.resolve(path);
+        match self.filesystem.get(&target) {
+            Some(FileNode::File(contents)) => contents.clone(),
+            Some(FileNode::Dir) => format!("cat: {path}: Is a directory\n"),
+            None => format!("cat: {path}: No such file or directory\n"),
+        }
+    }
+
+    fn mkdir(&mut self, path: Option<&str>) -> String {
+        let Some(path) = path else {
+            return "mkdir: missing operand\n".to_string();
+        };
+        let target = self.resolve(path);
+        self.filesystem.entry(target).or_insert(FileNode::Dir);
+        String::new()
+    }
+
+    fn write_file(&mut self, path: &str, text: &str, append: bool) -> String {
+        let target = self.resolve(path);
+        let line = format!("{}\n", unquote(text));
+        match self.filesystem.get_mut(&target) {
+            Some(FileNode::File(contents)) if append => {
+                contents.push_str(&line);
+            }
+            _ => {
+                self.filesystem.insert(target, FileNode::File(line));
+            }
+        }
+        String::new()
+    }
+
+    /// `wget`/`curl` never actually reach out; they just report a
+    /// plausible-looking transfer and drop a placeholder file named after
+    /// the URL's last path segment, so a follow-up `ls`/`cat` still looks
+    /// consistent.
+    fn fetch(&mut self, cmd: &str, url: Option<&str>) -> String {
+        let Some(url) = url else {
+            return format!("{cmd}: missing URL\n");
+        };
+        let filename = url.rsplit('/').next().filter(|s| !s.is_empty()).unwrap_or("index.html");
+        let target = self.resolve(filename);
+        self.filesystem
+            .insert(target, FileNode::File("(binary data)\n".to_string()));
+
+        match cmd {
+            "curl" => "  % Total    % Received % Xferd  Average Speed   Time\n100  1024  100  1024    0     0   4096      0 --:--:-- --:--:-- --:--:-- 4096\n".to_string(),
+            _ => format!("Saving to: '{filename}'\n\n{filename}  100%[===================>]  1.00K  --.-KB/s    in 0s\n\n'{filename}' saved\n"),
+        }
+    }
+}
+
+/// Split `echo`'s argument on a `>>`/`>` redirection, returning
+/// `(text, target_path, append)`. Returns `None` when there's no
+/// redirection, in which case the caller just echoes the text.
+fn split_redirection(rest: &str) -> Option<(String, String, bool)> {
+    if let Some((text, target)) = rest.split_once(">>") {
+        Some((text.trim().to_string(), target.trim().to_string(), true))
+    } else if let Some((text, target)) = rest.split_once('>') {
+        Some((text.trim().to_string(), target.trim().to_string(), false))
+    } else {
+        None
+    }
+}
+
+fn unquote(text: &str) -> String {
+    let trimmed = text.trim();
+    if trimmed.len() >= 2
+        && ((trimmed.starts_with('"') && trimmed.ends_with('"'))
+            || (trimmed.starts_with('\'') && trimmed.ends_with('\'')))
+    {
+        trimmed[1..trimmed.len() - 1].to_string()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session() -> ShellSession {
+        ShellSession::new("127.0.0.1:1234".parse().unwrap())
+    }
+
+    #[test]
+    fn whoami_and_uname_report_fake_identity() {
+        let mut s = session();
+        assert_eq!(s.interpret("whoami"), "deploy\n");
+        assert!(s.interpret("uname -a").contains(HOSTNAME));
+    }
+
+    #[test]
+    fn cat_reads_seeded_passwd_file() {
+        let mut s = session();
+        let output = s.interpret("cat /etc/passwd");
+        assert!(output.contains("root:x:0:0"));
+        assert!(output.contains(USERNAME));
+    }
+
+    #[test]
+    fn cd_and_relative_paths_update_cwd() {
+        let mut s = session();
+        assert_eq!(s.cwd, "/home/deploy");
+        s.interpret("cd /etc");
+        assert_eq!(s.cwd, "/etc");
+        s.interpret("cd ..");
+        assert_eq!(s.cwd, "/");
+    }
+
+    #[test]
+    fn cd_into_missing_directory_errors() {
+        let mut s = session();
+        let output = s.interpret("cd /nope");
+        assert!(output.contains("No such file or directory"));
+        assert_eq!(s.cwd, "/home/deploy"); // unchanged
+    }
+
+    #[test]
+    fn mkdir_then_echo_append_then_cat_is_consistent() {
+        let mut s = session();
+        s.interpret("mkdir scratch");
+        s.interpret("echo first >> /home/deploy/scratch/notes.txt");
+        s.interpret("echo second >> /home/deploy/scratch/notes.txt");
+        let output = s.interpret("cat /home/deploy/scratch/notes.txt");
+        assert_eq!(output, "first\nsecond\n");
+    }
+
+    #[test]
+    fn ls_lists_seeded_home_directory_entries() {
+        let mut s = session();
+        let output = s.interpret("ls");
+        assert!(output.contains(".bashrc"));
+    }
+
+    #[test]
+    fn unknown_command_reports_not_found() {
+        let mut s = session();
+        assert_eq!(s.interpret("frobnicate"), "bash: frobnicate: command not found\n");
+    }
+
+    #[test]
+    fn execute_classifies_malicious_command_as_threat() {
+        let mut s = session();
+        let (_, threat) = s.execute("cat /etc/passwd; rm -rf /");
+        assert!(threat.is_some());
+        assert_eq!(threat.unwrap().event_type, "command_injection");
+    }
+
+    #[test]
+    fn execute_appends_prompt_to_output() {
+        let mut s = session();
+        let (output, _) = s.execute("whoami");
+        assert!(output.ends_with(&s.prompt()));
+    }
+}
@@ -0,0 +1,298 @@
+// Real-time threat feed over WebSocket
+// -------------------------------------
+// The stats server on 7879 only answers one-shot HTTP GETs, so a SIEM
+// collector that wants live events has to poll it. This exposes a
+// WebSocket endpoint: a client sends a `subscribe` frame (optionally
+// filtering by minimum severity or event_type), then receives
+// `ThreatEvent`s and connection open/close notices as they happen. All
+// publishers (`handle_client`, and whatever calls `analyze_for_threats`)
+// push into one `tokio::sync::broadcast` channel so they never block on
+// a slow or absent subscriber, and idle connections get a periodic
+// heartbeat frame so load balancers and quiet collectors don't reap them.
+
+use crate::ThreatEvent;
+use chrono::{DateTime, Utc};
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+/// Everything the feed can publish. Threat events and connection
+/// lifecycle notices share one channel/enum so a single subscription
+/// sees one consistent timeline instead of needing to merge two feeds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FeedEvent {
+    Threat(ThreatEvent),
+    ConnectionOpened {
+        id: String,
+        peer_addr: String,
+        at: DateTime<Utc>,
+    },
+    ConnectionClosed {
+        id: String,
+        at: DateTime<Utc>,
+    },
+}
+
+impl FeedEvent {
+    fn severity(&self) -> Option<&str> {
+        match self {
+            FeedEvent::Threat(t) => Some(t.severity.as_str()),
+            FeedEvent::ConnectionOpened { .. } | FeedEvent::ConnectionClosed { .. } => None,
+        }
+    }
+
+    fn event_type(&self) -> Option<&str> {
+        match self {
+            FeedEvent::Threat(t) => Some(t.event_type.as_str()),
+            FeedEvent::ConnectionOpened { .. } | FeedEvent::ConnectionClosed { .. } => None,
+        }
+    }
+}
+
+/// Severity rank used for `min_severity` filtering; higher is more severe.
+/// Matches the vocabulary `protocol::analyze_for_threats` produces.
+fn severity_rank(severity: &str) -> u8 {
+    match severity {
+        "critical" => 3,
+        "high" => 2,
+        "medium" => 1,
+        "low" => 0,
+        _ => 0,
+    }
+}
+
+const DEFAULT_CAPACITY: usize = 1024;
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Broadcast hub: one `Sender` shared across every connection handler and
+/// the detection pipeline; each WebSocket subscriber gets its own
+/// `Receiver` via `subscribe`.
+pub struct FeedHub {
+    sender: broadcast::Sender<FeedEvent>,
+}
+
+impl FeedHub {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(DEFAULT_CAPACITY);
+        Self { sender }
+    }
+
+    /// Publish an event to every current subscriber. A `send` error here
+    /// only ever means "nobody is listening right now", which is a
+    /// perfectly normal state, not a failure worth reporting.
+    pub fn publish(&self, event: FeedEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    pub fn publish_threat(&self, event: ThreatEvent) {
+        self.publish(FeedEvent::Threat(event));
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<FeedEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for FeedHub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Subscribe request a client sends as the first WebSocket text frame.
+/// Missing fields mean "no filter on this dimension"; an unparseable or
+/// non-text first frame just means the client gets everything. Nothing is
+/// relayed until the client has sent that first frame.
+#[derive(Debug, Default, Deserialize)]
+struct SubscribeRequest {
+    #[serde(default)]
+    #[allow(dead_code)] // reserved for future multi-channel routing
+    channel: Option<String>,
+    #[serde(default)]
+    min_severity: Option<String>,
+    #[serde(default)]
+    event_type: Option<String>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct Filter {
+    min_severity_rank: Option<u8>,
+    event_type: Option<String>,
+}
+
+impl Filter {
+    fn from_request(req: &SubscribeRequest) -> Self {
+        Self {
+            min_severity_rank: req.min_severity.as_deref().map(severity_rank),
+            event_type: req.event_type.clone(),
+        }
+    }
+
+    fn matches(&self, event: &FeedEvent) -> bool {
+        if let Some(min_rank) = self.min_severity_rank {
+            if let Some(sev) = event.severity() {
+                if severity_rank(sev) < min_rank {
+                    return false;
+                }
+            }
+        }
+
+        if let Some(ref want) = self.event_type {
+            if let Some(et) = event.event_type() {
+                if et != want {
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+}
+
+/// Drive one accepted WebSocket connection: read the initial subscribe
+/// frame, then relay broadcast events matching its filter plus periodic
+/// heartbeats until the client disconnects or a send fails.
+async fn handle_connection(stream: WebSocketStream<TcpStream>, hub: Arc<FeedHub>) {
+    let (mut write, mut read) = stream.split();
+
+    let filter = match read.next().await {
+        Some(Ok(Message::Text(text))) => serde_json::from_str::<SubscribeRequest>(&text)
+            .map(|req| Filter::from_request(&req))
+            .unwrap_or_default(),
+        _ => Filter::default(),
+    };
+
+    let mut rx = hub.subscribe();
+    let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+    heartbeat.tick().await; // first tick fires immediately; skip it
+
+    loop {
+        tokio::select! {
+            event = rx.recv() => {
+                match event {
+                    Ok(event) => {
+                        if !filter.matches(&event) {
+                            continue;
+                        }
+                        let Ok(payload) = serde_json::to_string(&event) else { continue };
+                        if write.send(Message::Text(payload)).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+            _ = heartbeat.tick() => {
+                if write.send(Message::Ping(Vec::new())).await.is_err() {
+                    break;
+                }
+            }
+            incoming = read.next() => {
+                match incoming {
+                    Some(Ok(Message::Close(_))) | None | Some(Err(_)) => break,
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// Accept loop for the feed's WebSocket listener. Intended to be spawned
+/// as its own task alongside the stats/TCP servers.
+pub async fn serve(addr: &str, hub: Arc<FeedHub>) -> io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    log::info!("Threat feed WebSocket listening on {addr}");
+
+    loop {
+        let (stream, peer_addr) = listener.accept().await?;
+        let hub = Arc::clone(&hub);
+        tokio::spawn(async move {
+            match tokio_tungstenite::accept_async(stream).await {
+                Ok(ws) => handle_connection(ws, hub).await,
+                Err(e) => log::warn!("WebSocket handshake with {peer_addr} failed: {e}"),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn threat(severity: &str, event_type: &str) -> FeedEvent {
+        FeedEvent::Threat(ThreatEvent {
+            event_id: "test".to_string(),
+            timestamp: Utc::now(),
+            source_ip: "1.2.3.4".to_string(),
+            event_type: event_type.to_string(),
+            description: "test".to_string(),
+            severity: severity.to_string(),
+            metadata: serde_json::json!({}),
+        })
+    }
+
+    #[test]
+    fn no_filter_matches_everything() {
+        let filter = Filter::default();
+        assert!(filter.matches(&threat("low", "reconnaissance")));
+    }
+
+    #[test]
+    fn min_severity_filters_out_lower_events() {
+        let filter = Filter {
+            min_severity_rank: Some(severity_rank("high")),
+            event_type: None,
+        };
+        assert!(!filter.matches(&threat("low", "reconnaissance")));
+        assert!(filter.matches(&threat("critical", "command_injection")));
+    }
+
+    #[test]
+    fn event_type_filter_is_exact_match() {
+        let filter = Filter {
+            min_severity_rank: None,
+            event_type: Some("sql_injection".to_string()),
+        };
+        assert!(filter.matches(&threat("low", "sql_injection")));
+        assert!(!filter.matches(&threat("low", "xss_attempt")));
+    }
+
+    #[test]
+    fn connection_lifecycle_events_pass_severity_filter() {
+        let filter = Filter {
+            min_severity_rank: Some(severity_rank("critical")),
+            event_type: None,
+        };
+        let event = FeedEvent::ConnectionOpened {
+            id: "conn-1".to_string(),
+            peer_addr: "127.0.0.1:1234".to_string(),
+            at: Utc::now(),
+        };
+        assert!(filter.matches(&event));
+    }
+
+    #[test]
+    fn hub_publish_without_subscribers_does_not_panic() {
+        let hub = FeedHub::new();
+        hub.publish(threat("low", "reconnaissance"));
+    }
+
+    #[tokio::test]
+    async fn subscriber_receives_published_event() {
+        let hub = FeedHub::new();
+        let mut rx = hub.subscribe();
+        hub.publish(threat("high", "command_injection"));
+
+        let received = rx.recv().await.expect("event should be delivered");
+        assert_eq!(received.severity(), Some("high"));
+    }
+}
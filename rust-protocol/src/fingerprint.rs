@@ -0,0 +1,239 @@
+// Passive TCP/IP stack fingerprinting
+// -------------------------------------
+// Layer 0's classifier only looks at payload bytes, but a deception layer
+// that "wants to see everything" should also fingerprint the peer's
+// stack, p0f-style, from nothing more than what a SYN/accepted socket
+// already exposes: observed IP TTL, TCP window size, MSS, window scale,
+// and the ordering of TCP options. This stays stateless and
+// observe-tag-never-judge: it only ever adds an `os_guess` and tags/score
+// to a `Layer0Output`, never drops.
+//
+// NOT YET WIRED IN: nothing calls `fingerprint_stack`/`apply_to_layer0`
+// outside this module's own tests. `main.rs`'s accept loop is a plain
+// `tokio::net::TcpListener`, which only ever exposes the fully-formed
+// stream — the per-SYN window/MSS/options triple this module needs has
+// to come from the raw packet, not the socket, so capturing it for real
+// would mean adding raw-socket or eBPF capture alongside the existing
+// `socket2` listener in `main()`, which is a bigger change than this
+// request's scope. It's also moot until `reducers.rs`'s `Layer0Output`
+// pipeline as a whole is wired into `main.rs`, which it isn't yet either
+// at baseline. Land that wiring first, then thread `SynMetadata` capture
+// into `handle_client` and call `apply_to_layer0` per connection.
+
+use crate::reducers::{tags, Layer0Output, Protocol};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// The handful of socket-level observables available at accept() time.
+#[derive(Debug, Clone)]
+pub struct SynMetadata {
+    pub observed_ttl: u8,
+    pub window_size: u16,
+    pub mss: Option<u16>,
+    pub window_scale: Option<u8>,
+    /// Ordering of observed TCP options, e.g. `["mss", "sok", "ts", "nop", "ws"]`.
+    pub options_layout: Vec<&'static str>,
+}
+
+/// The derived signature, independent of any DB match.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StackSignature {
+    pub initial_ttl: u8,
+    pub hop_count: u8,
+    pub window_mss_ratio: f64,
+    pub options_hash: u64,
+}
+
+/// Round an observed TTL up to the nearest common initial TTL, recovering
+/// what the sender's stack most likely started with before routers
+/// decremented it.
+pub fn likely_initial_ttl(observed_ttl: u8) -> u8 {
+    const COMMON_INITIAL_TTLS: [u8; 4] = [32, 64, 128, 255];
+    COMMON_INITIAL_TTLS
+        .into_iter()
+        .find(|&candidate| observed_ttl <= candidate)
+        .unwrap_or(255)
+}
+
+pub fn hop_count(observed_ttl: u8, initial_ttl: u8) -> u8 {
+    initial_ttl.saturating_sub(observed_ttl)
+}
+
+/// Window size relative to MSS; real stacks cluster window into a handful
+/// of multiples of MSS, while raw-socket scanners often set window == MSS.
+pub fn window_mss_ratio(window_size: u16, mss: u16) -> f64 {
+    if mss == 0 {
+        0.0
+    } else {
+        window_size as f64 / mss as f64
+    }
+}
+
+pub fn hash_options_layout(layout: &[&str]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    layout.join(",").hash(&mut hasher);
+    hasher.finish()
+}
+
+/// One entry in the embedded p0f-style signature DB.
+struct OsEntry {
+    name: &'static str,
+    initial_ttl: u8,
+    ratio_range: (f64, f64),
+    options_layout: &'static [&'static str],
+}
+
+/// Tiny embedded signature DB. Not exhaustive — good enough for routing
+/// hints, not attribution.
+const OS_DB: &[OsEntry] = &[
+    OsEntry {
+        name: "Linux (generic)",
+        initial_ttl: 64,
+        ratio_range: (40.0, 46.0),
+        options_layout: &["mss", "sok", "ts", "nop", "ws"],
+    },
+    OsEntry {
+        name: "Windows",
+        initial_ttl: 128,
+        ratio_range: (500.0, 520.0),
+        options_layout: &["mss", "nop", "ws", "nop", "nop", "sok"],
+    },
+    OsEntry {
+        name: "macOS/BSD",
+        initial_ttl: 64,
+        ratio_range: (14.0, 18.0),
+        options_layout: &["mss", "nop", "ws", "nop", "nop", "ts", "sok", "eol"],
+    },
+    OsEntry {
+        name: "nmap-style raw socket scan",
+        initial_ttl: 64,
+        ratio_range: (0.9, 1.1),
+        options_layout: &["mss"],
+    },
+];
+
+#[derive(Debug, Clone)]
+pub struct StackFingerprint {
+    pub os_guess: Option<&'static str>,
+    pub signature: StackSignature,
+    /// True when the stack signature is inconsistent with the protocol
+    /// the payload claims to be (e.g. an nmap-style window paired with an
+    /// HTTP banner) — a strong tell of a raw-socket prober.
+    pub synthetic: bool,
+}
+
+/// Build a passive stack signature and match it against the embedded DB.
+pub fn fingerprint_stack(meta: &SynMetadata, claimed_protocol: Protocol) -> StackFingerprint {
+    let initial_ttl = likely_initial_ttl(meta.observed_ttl);
+    let hop_count = hop_count(meta.observed_ttl, initial_ttl);
+    let window_mss_ratio = meta
+        .mss
+        .map(|mss| window_mss_ratio(meta.window_size, mss))
+        .unwrap_or(0.0);
+    let options_hash = hash_options_layout(&meta.options_layout);
+
+    let matched = OS_DB.iter().find(|entry| {
+        entry.initial_ttl == initial_ttl
+            && window_mss_ratio >= entry.ratio_range.0
+            && window_mss_ratio <= entry.ratio_range.1
+            && entry.options_layout == meta.options_layout.as_slice()
+    });
+
+    // A raw-socket scanner tends to leave window == MSS (ratio ~1.0) since
+    // it never goes through a real TCP stack's window-scaling logic; that
+    // is consistent with scanning but inconsistent with a real HTTP stack.
+    let synthetic = claimed_protocol == Protocol::HTTP && (0.9..=1.1).contains(&window_mss_ratio);
+
+    StackFingerprint {
+        os_guess: matched.map(|entry| entry.name),
+        signature: StackSignature {
+            initial_ttl,
+            hop_count,
+            window_mss_ratio,
+            options_hash,
+        },
+        synthetic,
+    }
+}
+
+/// Fold a fingerprint into a `Layer0Output`: sets `os_guess`, adds
+/// `SYNTHETIC_STACK` plus score when the signature contradicts the
+/// claimed protocol, and a small score bump for any confident OS match.
+/// Never drops or escalates by itself — only tags and scores.
+pub fn apply_to_layer0(fingerprint: &StackFingerprint, out: &mut Layer0Output) {
+    out.os_guess = fingerprint.os_guess;
+
+    if fingerprint.synthetic {
+        out.add_tag(tags::SYNTHETIC_STACK);
+        out.add_score(30);
+    } else if fingerprint.os_guess.is_some() {
+        out.add_score(5);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rounds_ttl_to_nearest_common_initial_value() {
+        assert_eq!(likely_initial_ttl(60), 64);
+        assert_eq!(likely_initial_ttl(120), 128);
+        assert_eq!(likely_initial_ttl(250), 255);
+        assert_eq!(likely_initial_ttl(255), 255);
+    }
+
+    #[test]
+    fn hop_count_is_initial_minus_observed() {
+        assert_eq!(hop_count(58, 64), 6);
+        assert_eq!(hop_count(64, 64), 0);
+    }
+
+    #[test]
+    fn matches_linux_generic_signature() {
+        let meta = SynMetadata {
+            observed_ttl: 58,
+            window_size: 64240,
+            mss: Some(1460),
+            window_scale: Some(7),
+            options_layout: vec!["mss", "sok", "ts", "nop", "ws"],
+        };
+
+        let fp = fingerprint_stack(&meta, Protocol::SSH);
+        assert_eq!(fp.os_guess, Some("Linux (generic)"));
+        assert!(!fp.synthetic);
+    }
+
+    #[test]
+    fn flags_synthetic_stack_for_scanner_window_with_http_banner() {
+        let meta = SynMetadata {
+            observed_ttl: 64,
+            window_size: 1460,
+            mss: Some(1460),
+            window_scale: None,
+            options_layout: vec!["mss"],
+        };
+
+        let fp = fingerprint_stack(&meta, Protocol::HTTP);
+        assert!(fp.synthetic);
+
+        let mut out = Layer0Output::new(Protocol::HTTP);
+        apply_to_layer0(&fp, &mut out);
+        assert_eq!(out.tags & tags::SYNTHETIC_STACK, tags::SYNTHETIC_STACK);
+        assert!(out.suspicion_score >= 30);
+    }
+
+    #[test]
+    fn unknown_signature_leaves_os_guess_none() {
+        let meta = SynMetadata {
+            observed_ttl: 200,
+            window_size: 1234,
+            mss: Some(500),
+            window_scale: None,
+            options_layout: vec!["weird"],
+        };
+
+        let fp = fingerprint_stack(&meta, Protocol::Unknown);
+        assert_eq!(fp.os_guess, None);
+    }
+}
@@ -0,0 +1,338 @@
+// Distributed gossip exchange
+// ----------------------------
+// Lets multiple Apate deployments share live threat intel: nodes
+// periodically exchange a digest of recently-seen malicious source IPs
+// (reusing the beacon codec), learn new peer addresses from received
+// messages, and merge remote observations into the local ban state so an
+// IP blocked at one sensor is known at every sensor.
+
+use crate::banlist::BanList;
+use crate::codec::{self, DecodeError};
+use crate::feed::{FeedEvent, FeedHub};
+use crate::ThreatEvent;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::UdpSocket;
+
+/// How many distinct fingerprints we remember for dedupe before the oldest
+/// entries are evicted.
+const SEEN_FINGERPRINT_CAP: usize = 4096;
+
+/// Large enough for a batch of beacon frames; bigger digests get
+/// truncated by `recv_from`, which surfaces as a decode error rather than
+/// silently dropping data.
+const MAX_DIGEST_SIZE: usize = 16 * 1024;
+
+#[derive(Debug)]
+pub enum GossipError {
+    /// The peer has exceeded its rate limit and the digest was dropped.
+    RateLimited,
+    Decode(DecodeError),
+}
+
+impl From<DecodeError> for GossipError {
+    fn from(err: DecodeError) -> Self {
+        GossipError::Decode(err)
+    }
+}
+
+struct PeerState {
+    window_start_ms: u64,
+    count_in_window: usize,
+}
+
+/// One node's view of the gossip mesh: known peers, a dedupe set to keep
+/// flooding from poisoning the shared view, and per-peer rate limiting.
+pub struct GossipNode {
+    /// Configured public addresses for nodes behind NAT that cannot
+    /// auto-detect their own endpoint. They are only stored and returned by
+    /// `advertise_addresses()`; digests do not carry them, so peers learn
+    /// this node from the source address of its datagrams.
+    advertise_addresses: Vec<SocketAddr>,
+    peers: Mutex<HashMap<SocketAddr, PeerState>>,
+    seen_fingerprints: Mutex<(HashSet<String>, VecDeque<String>)>,
+    rate_limit_per_peer: usize,
+    rate_window_ms: u64,
+}
+
+impl GossipNode {
+    pub fn new(advertise_addresses: Vec<SocketAddr>, rate_limit_per_peer: usize, rate_window_ms: u64) -> Self {
+        Self {
+            advertise_addresses,
+            peers: Mutex::new(HashMap::new()),
+            seen_fingerprints: Mutex::new((HashSet::new(), VecDeque::new())),
+            rate_limit_per_peer,
+            rate_window_ms,
+        }
+    }
+
+    /// Default tuning: 200 digests/min per peer.
+    pub fn with_defaults(advertise_addresses: Vec<SocketAddr>) -> Self {
+        Self::new(advertise_addresses, 200, 60_000)
+    }
+
+    pub fn advertise_addresses(&self) -> &[SocketAddr] {
+        &self.advertise_addresses
+    }
+
+    pub fn learn_peer(&self, addr: SocketAddr) {
+        let mut peers = self.peers.lock().unwrap();
+        peers.entry(addr).or_insert_with(|| PeerState {
+            window_start_ms: current_time_ms(),
+            count_in_window: 0,
+        });
+    }
+
+    pub fn known_peers(&self) -> Vec<SocketAddr> {
+        self.peers.lock().unwrap().keys().copied().collect()
+    }
+
+    /// Encode a set of recently-observed threat events into a single
+    /// digest: a sequence of beacon frames, each prefixed with its length.
+    pub fn encode_digest(events: &[ThreatEvent]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        for event in events {
+            let frame = codec::encode_beacon(event);
+            buf.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            buf.extend_from_slice(&frame);
+        }
+        buf
+    }
+
+    fn decode_digest(bytes: &[u8]) -> Result<Vec<ThreatEvent>, DecodeError> {
+        let mut events = Vec::new();
+        let mut pos = 0usize;
+
+        while pos < bytes.len() {
+            if pos + 4 > bytes.len() {
+                return Err(DecodeError::Truncated);
+            }
+            let len = u32::from_le_bytes(bytes[pos..pos + 4].try_into().unwrap()) as usize;
+            pos += 4;
+
+            if pos + len > bytes.len() {
+                return Err(DecodeError::Truncated);
+            }
+            events.push(codec::decode_beacon(&bytes[pos..pos + len])?);
+            pos += len;
+        }
+
+        Ok(events)
+    }
+
+    fn fingerprint_key(event: &ThreatEvent) -> String {
+        event
+            .metadata
+            .get("fingerprint")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| event.event_id.clone())
+    }
+
+    /// Check and record this peer's digest against the rate limit. Returns
+    /// false (and drops the digest) if the peer is flooding.
+    fn admit(&self, from: SocketAddr) -> bool {
+        let now = current_time_ms();
+        let mut peers = self.peers.lock().unwrap();
+        let state = peers.entry(from).or_insert_with(|| PeerState {
+            window_start_ms: now,
+            count_in_window: 0,
+        });
+
+        if now.saturating_sub(state.window_start_ms) > self.rate_window_ms {
+            state.window_start_ms = now;
+            state.count_in_window = 0;
+        }
+
+        state.count_in_window += 1;
+        state.count_in_window <= self.rate_limit_per_peer
+    }
+
+    /// Returns true if this fingerprint is new (and records it), false if
+    /// it has already been merged.
+    fn dedupe(&self, key: &str) -> bool {
+        let mut seen = self.seen_fingerprints.lock().unwrap();
+        if seen.0.contains(key) {
+            return false;
+        }
+
+        seen.0.insert(key.to_string());
+        seen.1.push_back(key.to_string());
+        if seen.1.len() > SEEN_FINGERPRINT_CAP {
+            if let Some(oldest) = seen.1.pop_front() {
+                seen.0.remove(&oldest);
+            }
+        }
+
+        true
+    }
+
+    /// Merge a digest received from a peer into the local ban list,
+    /// subject to the anti-flood guard; no reducer state is updated. Returns the newly-merged
+    /// events (duplicates and rate-limited digests are silently dropped).
+    pub fn ingest(&self, from: SocketAddr, digest: &[u8], banlist: &BanList) -> Result<Vec<ThreatEvent>, GossipError> {
+        if !self.admit(from) {
+            return Err(GossipError::RateLimited);
+        }
+
+        self.learn_peer(from);
+
+        let events = Self::decode_digest(digest)?;
+        let mut merged = Vec::with_capacity(events.len());
+
+        for event in events {
+            if self.dedupe(&Self::fingerprint_key(&event)) {
+                banlist.ingest(&event);
+                merged.push(event);
+            }
+        }
+
+        Ok(merged)
+    }
+}
+
+fn current_time_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_millis() as u64
+}
+
+/// Run the gossip transport: a send task that drains newly-observed
+/// threats off `feed_hub` and pushes a digest to every known peer every
+/// `digest_interval`, plus a receive loop on the same socket that merges
+/// whatever peers send back. Seed peers are learned by the caller (via
+/// `node.learn_peer`) before this is spawned. Intended to run as its own
+/// task alongside the stats/feed servers.
+pub async fn serve(
+    bind_addr: &str,
+    node: Arc<GossipNode>,
+    banlist: Arc<BanList>,
+    feed_hub: Arc<FeedHub>,
+    digest_interval: Duration,
+) -> io::Result<()> {
+    let socket = Arc::new(UdpSocket::bind(bind_addr).await?);
+    log::info!("Gossip socket listening on {bind_addr}");
+
+    let send_socket = Arc::clone(&socket);
+    let send_node = Arc::clone(&node);
+    tokio::spawn(async move {
+        let mut rx = feed_hub.subscribe();
+        let mut pending = Vec::new();
+        let mut ticker = tokio::time::interval(digest_interval);
+
+        loop {
+            tokio::select! {
+                event = rx.recv() => {
+                    match event {
+                        Ok(FeedEvent::Threat(threat)) => pending.push(threat),
+                        Ok(_) => {}
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+                _ = ticker.tick() => {
+                    if pending.is_empty() {
+                        continue;
+                    }
+                    let digest = GossipNode::encode_digest(&pending);
+                    pending.clear();
+
+                    for peer in send_node.known_peers() {
+                        if let Err(e) = send_socket.send_to(&digest, peer).await {
+                            log::warn!("failed to send gossip digest to {peer}: {e}");
+                        }
+                    }
+                }
+            }
+        }
+    });
+
+    let mut buf = vec![0u8; MAX_DIGEST_SIZE];
+    loop {
+        let (n, peer) = socket.recv_from(&mut buf).await?;
+        match node.ingest(peer, &buf[..n], &banlist) {
+            Ok(merged) if !merged.is_empty() => {
+                log::debug!("merged {} threat(s) from gossip peer {peer}", merged.len());
+            }
+            Ok(_) => {}
+            Err(GossipError::RateLimited) => {
+                log::warn!("dropped gossip digest from {peer}: rate limited");
+            }
+            Err(GossipError::Decode(e)) => {
+                log::warn!("dropped malformed gossip digest from {peer}: {e:?}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn event(ip: &str, fingerprint: &str) -> ThreatEvent {
+        ThreatEvent {
+            event_id: format!("evt-{}", ip),
+            timestamp: Utc::now(),
+            source_ip: ip.to_string(),
+            event_type: "reconnaissance".to_string(),
+            description: "test".to_string(),
+            severity: "low".to_string(),
+            metadata: serde_json::json!({ "fingerprint": fingerprint }),
+        }
+    }
+
+    #[test]
+    fn digest_round_trips() {
+        let events = vec![event("1.1.1.1", "fp-a"), event("2.2.2.2", "fp-b")];
+        let digest = GossipNode::encode_digest(&events);
+        let decoded = GossipNode::decode_digest(&digest).unwrap();
+
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[0].source_ip, "1.1.1.1");
+        assert_eq!(decoded[1].source_ip, "2.2.2.2");
+    }
+
+    #[test]
+    fn ingest_merges_new_events_and_learns_peer() {
+        let node = GossipNode::with_defaults(vec![]);
+        let banlist = BanList::new(600_000, 100, 1_000, 60_000);
+        let peer: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+
+        let digest = GossipNode::encode_digest(&[event("3.3.3.3", "fp-c")]);
+        let merged = node.ingest(peer, &digest, &banlist).unwrap();
+
+        assert_eq!(merged.len(), 1);
+        assert!(node.known_peers().contains(&peer));
+    }
+
+    #[test]
+    fn ingest_dedupes_by_fingerprint() {
+        let node = GossipNode::with_defaults(vec![]);
+        let banlist = BanList::new(600_000, 100, 1_000, 60_000);
+        let peer: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+
+        let digest = GossipNode::encode_digest(&[event("4.4.4.4", "fp-d")]);
+        assert_eq!(node.ingest(peer, &digest, &banlist).unwrap().len(), 1);
+        assert_eq!(node.ingest(peer, &digest, &banlist).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn ingest_rate_limits_flooding_peers() {
+        let node = GossipNode::new(vec![], 2, 60_000);
+        let banlist = BanList::new(600_000, 100, 1_000, 60_000);
+        let peer: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+
+        let d1 = GossipNode::encode_digest(&[event("5.5.5.1", "fp-1")]);
+        let d2 = GossipNode::encode_digest(&[event("5.5.5.2", "fp-2")]);
+        let d3 = GossipNode::encode_digest(&[event("5.5.5.3", "fp-3")]);
+
+        assert!(node.ingest(peer, &d1, &banlist).is_ok());
+        assert!(node.ingest(peer, &d2, &banlist).is_ok());
+        assert!(matches!(node.ingest(peer, &d3, &banlist), Err(GossipError::RateLimited)));
+    }
+}
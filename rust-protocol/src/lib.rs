@@ -2,8 +2,20 @@
 // --------------------
 // Common utilities and types for the Rust protocol server
 
+pub mod audit;
+pub mod banlist;
+pub mod banmgr;
 pub mod circuit_breaker;
+pub mod codec;
+pub mod config;
+pub mod crypto;
+pub mod emulation;
+pub mod feed;
+pub mod fingerprint;
+pub mod gossip;
 pub mod protocol;
+pub mod systemd;
+pub mod tls;
 pub mod utils;
 pub mod reducers;
 
@@ -13,12 +25,38 @@ use pyo3::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::net::SocketAddr;
 
+/// Which protocol dialect a `ProtocolMessage` was observed speaking.
+/// Replaces the old stringly-typed `message_type` so downstream matching
+/// can't silently typo past a variant; legacy short-form strings (e.g.
+/// `"http"`, `"ssh"`) are still accepted on deserialize via `alias`, and
+/// anything unrecognized falls back to `Unknown` instead of failing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageType {
+    #[serde(alias = "ssh")]
+    SshHandshake,
+    #[serde(alias = "http")]
+    HttpRequest,
+    #[serde(alias = "ftp")]
+    FtpCommand,
+    #[serde(alias = "smtp")]
+    SmtpCommand,
+    #[serde(other)]
+    Unknown,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProtocolMessage {
     pub id: String,
     pub timestamp: DateTime<Utc>,
     pub source: SocketAddr,
-    pub message_type: String,
+    pub message_type: MessageType,
+    /// Dialect version parsed out of the handshake/request line itself,
+    /// e.g. the `2.0` in `SSH-2.0-OpenSSH_8.9p1` or the `1.1` in
+    /// `HTTP/1.1`. `None` when the payload didn't carry one (FTP/SMTP
+    /// commands, or an unrecognized message type).
+    #[serde(default)]
+    pub protocol_version: Option<String>,
     pub payload: String,
     pub fingerprint: Option<String>,
 }
@@ -57,6 +95,11 @@ mod python_bindings {
         m.add_function(wrap_pyfunction!(generate_fingerprint_py, m)?)?;
         m.add_function(wrap_pyfunction!(detect_threats_py, m)?)?;
         m.add_function(wrap_pyfunction!(get_circuit_breaker_status_py, m)?)?;
+        m.add_function(wrap_pyfunction!(check_ban_py, m)?)?;
+        m.add_function(wrap_pyfunction!(ingest_threat_event_py, m)?)?;
+        m.add_function(wrap_pyfunction!(seal_message_py, m)?)?;
+        m.add_function(wrap_pyfunction!(open_message_py, m)?)?;
+        m.add_function(wrap_pyfunction!(classify_payload_py, m)?)?;
 
         Ok(())
     }
@@ -94,7 +137,7 @@ mod python_bindings {
 
         // Release GIL for potentially expensive calculation
         py.allow_threads(|| {
-            let result = panic::catch_unwind(|| Ok(utils::calculate_entropy(data)));
+            let result = panic::catch_unwind(|| Ok(utils::calculate_entropy(data.as_bytes())));
 
             match result {
                 Ok(val) => val,
@@ -105,6 +148,30 @@ mod python_bindings {
         })
     }
 
+    /// Classify a payload's likely encoding (plaintext/base64/hex/
+    /// compressed/encrypted/unknown) from combined entropy + histogram
+    /// statistics. Returns the variant name as a string.
+    #[pyfunction]
+    #[pyo3(name = "classify_payload")]
+    fn classify_payload_py(py: Python, data: &str) -> PyResult<String> {
+        if data.len() > 1_000_000 {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "Input data too large for payload classification",
+            ));
+        }
+
+        py.allow_threads(|| {
+            let result = panic::catch_unwind(|| {
+                let class = utils::classify_payload(data.as_bytes());
+                format!("{:?}", class)
+            });
+
+            result.map_err(|_| {
+                pyo3::exceptions::PyRuntimeError::new_err("Rust panic in classify_payload")
+            })
+        })
+    }
+
     #[pyfunction]
     #[pyo3(name = "generate_fingerprint")]
     fn generate_fingerprint_py(data: &str) -> PyResult<String> {
@@ -162,7 +229,8 @@ mod python_bindings {
                     id: "temp".to_string(),
                     timestamp: Utc::now(),
                     source: source_addr,
-                    message_type: "unknown".to_string(),
+                    message_type: MessageType::Unknown,
+                    protocol_version: None,
                     payload,
                     fingerprint: None,
                 };
@@ -195,4 +263,115 @@ mod python_bindings {
     fn get_circuit_breaker_status_py() -> String {
         CIRCUIT_BREAKER.get_state_name().to_string()
     }
+
+    use crate::banlist::BanList;
+    use std::sync::OnceLock;
+
+    /// This module has no async runtime of its own (Python drives it
+    /// synchronously via the GIL), so the background-eviction pass
+    /// `BanList::evict_expired` needs is a plain OS thread, spawned once
+    /// alongside the list itself, playing the same role here that a
+    /// `tokio::spawn` ticker plays for `main.rs`'s own `BanManager`.
+    fn global_banlist() -> &'static BanList {
+        static CELL: OnceLock<BanList> = OnceLock::new();
+        static EVICTOR_SPAWNED: OnceLock<()> = OnceLock::new();
+
+        let banlist = CELL.get_or_init(BanList::with_defaults);
+        EVICTOR_SPAWNED.get_or_init(|| {
+            std::thread::spawn(|| loop {
+                std::thread::sleep(std::time::Duration::from_secs(30));
+                global_banlist().evict_expired();
+            });
+        });
+        banlist
+    }
+
+    /// Remaining ban time in milliseconds for `ip`, or None if not banned.
+    #[pyfunction]
+    #[pyo3(name = "check_ban")]
+    fn check_ban_py(ip: &str) -> PyResult<Option<u64>> {
+        if ip.len() > 45 {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "IP address too long",
+            ));
+        }
+
+        let result = panic::catch_unwind(|| global_banlist().check_ban(ip));
+
+        result.map_err(|_| pyo3::exceptions::PyRuntimeError::new_err("Rust panic in check_ban"))
+    }
+
+    /// Feed a ThreatEvent (as JSON) into the ban list; returns the rendered
+    /// nftables rule text if this event crossed the ban threshold.
+    #[pyfunction]
+    #[pyo3(name = "ingest_threat_event")]
+    fn ingest_threat_event_py(event_json: &str) -> PyResult<Option<String>> {
+        if event_json.len() > 1_000_000 {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "Event payload too large",
+            ));
+        }
+
+        let event: ThreatEvent = serde_json::from_str(event_json)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Invalid ThreatEvent: {}", e)))?;
+
+        let result = panic::catch_unwind(|| global_banlist().ingest(&event).map(|d| d.rule_text));
+
+        result.map_err(|_| pyo3::exceptions::PyRuntimeError::new_err("Rust panic in ingest_threat_event"))
+    }
+
+    use crate::crypto;
+
+    fn key_from_bytes(key: &[u8]) -> PyResult<[u8; 32]> {
+        key.try_into()
+            .map_err(|_| pyo3::exceptions::PyValueError::new_err("key must be exactly 32 bytes"))
+    }
+
+    /// Seal a ProtocolMessage (given as JSON) into an AEAD envelope.
+    #[pyfunction]
+    #[pyo3(name = "seal_message")]
+    fn seal_message_py(message_json: &str, key: &[u8]) -> PyResult<Vec<u8>> {
+        if message_json.len() > 1_000_000 {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "Message payload too large to seal",
+            ));
+        }
+
+        let key = key_from_bytes(key)?;
+        let message: ProtocolMessage = serde_json::from_str(message_json)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Invalid ProtocolMessage: {}", e)))?;
+
+        let result = panic::catch_unwind(|| crypto::seal(&message, &key));
+
+        result.map_err(|_| pyo3::exceptions::PyRuntimeError::new_err("Rust panic in seal_message"))
+    }
+
+    /// Open a sealed frame, returning the recovered ProtocolMessage as JSON.
+    #[pyfunction]
+    #[pyo3(name = "open_message")]
+    fn open_message_py(frame: &[u8], key: &[u8]) -> PyResult<String> {
+        if frame.len() > 1_000_000 {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "Sealed frame too large",
+            ));
+        }
+
+        let key = key_from_bytes(key)?;
+
+        let result = panic::catch_unwind(|| {
+            crypto::open(frame, &key)
+                .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+                .and_then(|msg| {
+                    serde_json::to_string(&msg)
+                        .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+                })
+        });
+
+        match result {
+            Ok(val) => val,
+            Err(_) => Err(pyo3::exceptions::PyRuntimeError::new_err(
+                "Rust panic in open_message",
+            )),
+        }
+    }
 } // end mod python_bindings
@@ -17,6 +17,21 @@ use uuid::Uuid;
 use chrono::{DateTime, Utc};
 use socket2::{Socket, Domain, Type, Protocol};
 use rand::Rng;
+use rust_protocol::audit::{self, AuditRecord, ConnectionRecord, PostgresSink};
+use rust_protocol::banlist::BanList;
+use rust_protocol::banmgr::BanManager;
+use rust_protocol::config::Config;
+use rust_protocol::emulation;
+use rust_protocol::feed::{FeedEvent, FeedHub};
+use rust_protocol::gossip::{self, GossipNode};
+use rust_protocol::protocol::{self, EmulatedVersions};
+use rust_protocol::systemd;
+use rust_protocol::ThreatEvent;
+use std::path::Path;
+use tokio::sync::mpsc;
+
+const CONFIG_PATH: &str = "apate.toml";
+const DEFAULT_AUDIT_SPILL_PATH: &str = "audit_spill.jsonl";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct Connection {
@@ -33,14 +48,41 @@ struct ServerStats {
     connections: Arc<Mutex<Vec<Connection>>>,
     total_connections: Arc<Mutex<u64>>,
     start_time: Instant,
+    banmgr: Arc<BanManager>,
+    feed_hub: Arc<FeedHub>,
+    versions: Arc<EmulatedVersions>,
+    jitter: (u64, u64),
+    audit_tx: mpsc::Sender<AuditRecord>,
 }
 
 impl ServerStats {
-    fn new() -> Self {
+    fn new(
+        banmgr: Arc<BanManager>,
+        feed_hub: Arc<FeedHub>,
+        versions: Arc<EmulatedVersions>,
+        jitter: (u64, u64),
+        audit_tx: mpsc::Sender<AuditRecord>,
+    ) -> Self {
         Self {
             connections: Arc::new(Mutex::new(Vec::new())),
             total_connections: Arc::new(Mutex::new(0)),
             start_time: Instant::now(),
+            banmgr,
+            feed_hub,
+            versions,
+            jitter,
+            audit_tx,
+        }
+    }
+
+    /// Publish a detected threat to the live feed, the ban manager, and
+    /// the durable audit sink in one place, so every call site reports it
+    /// the same way instead of repeating the fan-out by hand.
+    fn report_threat(&self, threat: &ThreatEvent) {
+        self.feed_hub.publish_threat(threat.clone());
+        self.banmgr.ingest(threat);
+        if let Err(e) = self.audit_tx.try_send(AuditRecord::Threat(threat.clone())) {
+            warn!("audit channel full or closed, dropping threat event: {e}");
         }
     }
 
@@ -65,13 +107,42 @@ impl ServerStats {
             *total += 1;
         }
 
+        self.feed_hub.publish(FeedEvent::ConnectionOpened {
+            id: connection_id.clone(),
+            peer_addr: peer_addr.to_string(),
+            at: Utc::now(),
+        });
+
         info!("New connection from {}: {}", peer_addr, connection_id);
         connection_id
     }
 
     async fn remove_connection(&self, connection_id: &str) {
-        let mut connections = self.connections.lock().await;
-        connections.retain(|conn| conn.id != connection_id);
+        let removed = {
+            let mut connections = self.connections.lock().await;
+            let pos = connections.iter().position(|conn| conn.id == connection_id);
+            pos.map(|i| connections.remove(i))
+        };
+
+        self.feed_hub.publish(FeedEvent::ConnectionClosed {
+            id: connection_id.to_string(),
+            at: Utc::now(),
+        });
+
+        if let Some(conn) = removed {
+            let record = ConnectionRecord {
+                id: conn.id,
+                peer_addr: conn.peer_addr.to_string(),
+                connected_at: conn.connected_at,
+                disconnected_at: Some(Utc::now()),
+                bytes_received: conn.bytes_received,
+                bytes_sent: conn.bytes_sent,
+            };
+            if let Err(e) = self.audit_tx.try_send(AuditRecord::Connection(record)) {
+                warn!("audit channel full or closed, dropping connection record: {e}");
+            }
+        }
+
         info!("Connection {} disconnected", connection_id);
     }
 
@@ -87,25 +158,37 @@ impl ServerStats {
     async fn get_stats(&self) -> serde_json::Value {
         let connections = self.connections.lock().await;
         let total = *self.total_connections.lock().await;
-        
+        let banned: Vec<serde_json::Value> = self
+            .banmgr
+            .banned_snapshot()
+            .into_iter()
+            .map(|(ip, ttl_seconds)| serde_json::json!({"ip": ip, "ttl_seconds": ttl_seconds}))
+            .collect();
+
         serde_json::json!({
             "uptime_seconds": self.start_time.elapsed().as_secs(),
             "active_connections": connections.len(),
             "total_connections": total,
-            "connections": *connections
+            "connections": *connections,
+            "banned_ips": banned
         })
     }
 }
 
 async fn handle_client(mut socket: TcpStream, peer_addr: SocketAddr, stats: Arc<ServerStats>) -> io::Result<()> {
     let connection_id = stats.add_connection(peer_addr).await;
-    
+
     // Split socket for reading and writing
     let (mut reader, mut writer) = socket.split();
     let mut buffer = vec![0; 1024];
-    
+
+    // Once the SSH handshake is seen, hand every subsequent line to the
+    // fake shell session instead of the flat `process_data` responder so
+    // an interactive attacker gets a believable, stateful shell.
+    let mut ssh_session: Option<emulation::ShellSession> = None;
+
     info!("Handling client connection: {}", connection_id);
-    
+
     loop {
         // Read data from client
         match reader.read(&mut buffer).await {
@@ -117,14 +200,30 @@ async fn handle_client(mut socket: TcpStream, peer_addr: SocketAddr, stats: Arc<
             Ok(n) => {
                 let received_data = &buffer[..n];
                 let received_str = String::from_utf8_lossy(received_data);
-                
+
                 debug!("Received from {}: {}", connection_id, received_str.trim());
-                
-                // Process the received data and generate response
-                let response = process_data(&received_str, &connection_id, peer_addr).await;
-                
-                // Add randomized jitter to defeat timing analysis (1-5ms)
-                let jitter = rand::thread_rng().gen_range(1..=5);
+
+                let response = if let Some(session) = ssh_session.as_mut() {
+                    let (output, threat) = session.execute(&received_str);
+                    if let Some(threat) = threat {
+                        stats.report_threat(&threat);
+                    }
+                    output
+                } else {
+                    let (response, threat) =
+                        process_data(&received_str, &connection_id, peer_addr, &stats.versions).await;
+                    if let Some(threat) = threat {
+                        stats.report_threat(&threat);
+                    }
+                    if received_str.trim().starts_with("SSH-") {
+                        ssh_session = Some(emulation::ShellSession::new(peer_addr));
+                    }
+                    response
+                };
+
+                // Add randomized jitter to defeat timing analysis
+                let (jitter_min, jitter_max) = stats.jitter;
+                let jitter = rand::thread_rng().gen_range(jitter_min..=jitter_max);
                 sleep(Duration::from_millis(jitter)).await;
 
                 // Echo back with potential modifications
@@ -153,52 +252,70 @@ async fn handle_client(mut socket: TcpStream, peer_addr: SocketAddr, stats: Arc<
     Ok(())
 }
 
-async fn process_data(data: &str, connection_id: &str, peer_addr: SocketAddr) -> String {
+async fn process_data(
+    data: &str,
+    connection_id: &str,
+    peer_addr: SocketAddr,
+    versions: &EmulatedVersions,
+) -> (String, Option<ThreatEvent>) {
     let data_trimmed = data.trim();
-    
+
+    // Classify the payload against the built-in and config-supplied
+    // detection rules (`analyze_for_threats`) up front, so a custom rule
+    // added via `--wizard` fires for every connection this responder
+    // handles, not just the post-SSH-handshake shell session.
+    let threat = protocol::parse_message(data.as_bytes(), peer_addr)
+        .ok()
+        .and_then(|message| protocol::analyze_for_threats(&message));
+
     // Log potential attack patterns
     if data_trimmed.contains("GET /") || data_trimmed.contains("POST /") {
         warn!("HTTP request detected on TCP port from {}: {}", peer_addr, data_trimmed);
-        return format!("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n");
+        return (versions.http_not_found(), threat);
     }
-    
+
     if data_trimmed.starts_with("SSH-") {
         warn!("SSH handshake attempt from {}: {}", peer_addr, data_trimmed);
-        return format!("SSH-2.0-OpenSSH_8.9p1\r\n");
+        return (versions.ssh_banner(), threat);
     }
-    
+
+    if data_trimmed.contains("FTP") {
+        warn!("FTP probe from {}: {}", peer_addr, data_trimmed);
+        return (versions.ftp_banner(), threat);
+    }
+
+    if data_trimmed.contains("SMTP") {
+        warn!("SMTP probe from {}: {}", peer_addr, data_trimmed);
+        return (versions.smtp_banner(), threat);
+    }
+
     // Check for common network scanning patterns
     if data_trimmed.is_empty() {
         debug!("Empty probe from {}", peer_addr);
-        return String::new();
+        return (String::new(), threat);
     }
-    
+
     if data_trimmed.len() == 1 && data_trimmed.as_bytes()[0] < 32 {
         debug!("Binary probe from {}", peer_addr);
-        return format!("ECHO_SRV_v1.0\n");
+        return (format!("ECHO_SRV_v1.0\n"), threat);
     }
-    
-    // Check for malicious payloads
-    let suspicious_patterns = [
-        "shellcode", "exploit", "payload", "metasploit", 
-        "reverse_shell", "bind_shell", "nc -", "bash -i"
-    ];
-    
-    for pattern in &suspicious_patterns {
-        if data_trimmed.to_lowercase().contains(pattern) {
-            warn!("Suspicious payload detected from {}: {}", peer_addr, pattern);
-            // Return misleading response to waste attacker time
-            return format!("Command not recognized. Use 'help' for available commands.\n");
-        }
+
+    if let Some(threat) = threat {
+        warn!("Suspicious payload detected from {}: {}", peer_addr, threat.event_type);
+        // Return misleading response to waste attacker time
+        return (
+            format!("Command not recognized. Use 'help' for available commands.\n"),
+            Some(threat),
+        );
     }
-    
+
     // Handle specific commands that might be sent to probe services
-    match data_trimmed.to_lowercase().as_str() {
+    let response = match data_trimmed.to_lowercase().as_str() {
         "help" => {
             format!("Available commands: echo, status, info, quit\n")
         }
         "status" => {
-            format!("Server status: Online | Uptime: {} seconds\n", 
+            format!("Server status: Online | Uptime: {} seconds\n",
                    std::time::SystemTime::now()
                        .duration_since(std::time::UNIX_EPOCH)
                        .unwrap_or_default()
@@ -212,17 +329,17 @@ async fn process_data(data: &str, connection_id: &str, peer_addr: SocketAddr) ->
         }
         _ => {
             // Default echo behavior with timestamp
-            format!("[{}] ECHO: {}\n", 
-                   chrono::Utc::now().format("%H:%M:%S"), 
+            format!("[{}] ECHO: {}\n",
+                   chrono::Utc::now().format("%H:%M:%S"),
                    data_trimmed)
         }
-    }
+    };
+    (response, None)
 }
 
-async fn start_stats_server(stats: Arc<ServerStats>) -> io::Result<()> {
-    let listener = TcpListener::bind("0.0.0.0:7879").await?;
-    info!("Stats server listening on 0.0.0.0:7879");
-    
+async fn start_stats_server(listener: TcpListener, stats: Arc<ServerStats>) -> io::Result<()> {
+    info!("Stats server listening on {}", listener.local_addr()?);
+
     loop {
         match listener.accept().await {
             Ok((mut socket, peer_addr)) => {
@@ -251,35 +368,194 @@ async fn start_stats_server(stats: Arc<ServerStats>) -> io::Result<()> {
 async fn main() -> io::Result<()> {
     // Initialize logging
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
-    
-    let bind_addr = "0.0.0.0:7878";
-    
+
+    if std::env::args().any(|arg| arg == "--wizard") {
+        let config = rust_protocol::config::run_wizard();
+        config
+            .save(Path::new(CONFIG_PATH))
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+        println!("Wrote configuration to {CONFIG_PATH}");
+        return Ok(());
+    }
+
+    let config = match Config::load(Path::new(CONFIG_PATH)) {
+        Ok(config) => config,
+        Err(e) => {
+            info!("No usable config at {CONFIG_PATH} ({e}); using built-in defaults");
+            Config::with_defaults()
+        }
+    };
+
+    if let Err(e) = config.validate() {
+        error!("Refusing to start with invalid configuration: {e}");
+        return Err(io::Error::new(io::ErrorKind::InvalidData, e.to_string()));
+    }
+
+    match config.compile_detection_rules() {
+        Ok(rules) => protocol::install_custom_rules(rules),
+        Err(e) => {
+            error!("Refusing to start with invalid detection rules: {e}");
+            return Err(io::Error::new(io::ErrorKind::InvalidData, e.to_string()));
+        }
+    }
+
+    let bind_addr = config.listen.echo.clone();
+
     // Create a custom socket with specific TTL to mimic Linux kernel behavior
     // This defeats basic nmap OS fingerprinting which often flags default socket behavior
     let socket = Socket::new(Domain::IPV4, Type::STREAM, Some(Protocol::TCP))?;
     socket.set_ttl(64)?; // Linux default TTL
     socket.set_reuse_address(true)?;
     socket.set_nonblocking(true)?;
-    
-    let address: SocketAddr = bind_addr.parse().unwrap();
+
+    // `config.validate()` above already confirmed this parses; an error
+    // here would mean that check regressed, not that the address is bad.
+    let address: SocketAddr = bind_addr
+        .parse()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("invalid listen.echo {bind_addr:?}: {e}")))?;
     socket.bind(&address.into())?;
     socket.listen(128)?;
-    
+
     let listener = TcpListener::from_std(socket.into())?;
-    
-    let stats = Arc::new(ServerStats::new());
-    
+    let stats_listener = TcpListener::bind(&config.listen.stats).await?;
+
+    // Threat events and connection records are handed off over an mpsc
+    // channel so handle_client never waits on database I/O; the worker
+    // batches them into Postgres/TimescaleDB and spills to disk if the
+    // database is unreachable.
+    let audit_connection_string = std::env::var("AUDIT_DATABASE_URL")
+        .unwrap_or_else(|_| "postgresql://localhost/apate".to_string());
+    let audit_spill_path =
+        std::env::var("AUDIT_SPILL_PATH").unwrap_or_else(|_| DEFAULT_AUDIT_SPILL_PATH.to_string());
+    let (audit_tx, _audit_handle) =
+        audit::spawn(PostgresSink::new(audit_connection_string), audit_spill_path);
+
+    let banmgr = Arc::new(BanManager::with_defaults());
+    let feed_hub = Arc::new(FeedHub::new());
+    let versions = Arc::new(EmulatedVersions {
+        ssh: config.banners.ssh.clone(),
+        http: config.banners.http.clone(),
+        ftp: config.banners.ftp.clone(),
+        smtp: config.banners.smtp.clone(),
+    });
+    let jitter = (config.jitter.min_ms, config.jitter.max_ms);
+    let stats = Arc::new(ServerStats::new(banmgr, Arc::clone(&feed_hub), versions, jitter, audit_tx));
+
     info!("TCP Echo Server starting on {}", bind_addr);
-    info!("Server stats available on http://0.0.0.0:7879");
-    
+    info!("Server stats available on http://{}", config.listen.stats);
+    info!("Threat feed available on ws://0.0.0.0:7880");
+
+    // Both the echo and stats listeners are bound at this point, so tell
+    // systemd (when running under it) that the unit is up.
+    systemd::notify_ready();
+
     // Start stats server in background
     let stats_clone = Arc::clone(&stats);
     tokio::spawn(async move {
-        if let Err(e) = start_stats_server(stats_clone).await {
+        if let Err(e) = start_stats_server(stats_listener, stats_clone).await {
             error!("Stats server error: {}", e);
         }
     });
-    
+
+    // Gossip: share digests of locally-observed threats with peer Apate
+    // deployments so an IP banned at one sensor is known at all of them.
+    // An empty bind address (the default) leaves this node standalone.
+    if !config.gossip.bind.is_empty() {
+        let advertise_addresses: Vec<SocketAddr> = config
+            .gossip
+            .advertise_addresses
+            .iter()
+            .filter_map(|addr| match addr.parse() {
+                Ok(a) => Some(a),
+                Err(e) => {
+                    warn!("ignoring invalid gossip.advertise_addresses entry {addr:?}: {e}");
+                    None
+                }
+            })
+            .collect();
+
+        let gossip_node = Arc::new(GossipNode::with_defaults(advertise_addresses));
+        for peer in &config.gossip.seed_peers {
+            match peer.parse::<SocketAddr>() {
+                Ok(addr) => gossip_node.learn_peer(addr),
+                Err(e) => warn!("ignoring invalid gossip.seed_peers entry {peer:?}: {e}"),
+            }
+        }
+
+        // A gossip-sourced ban list is its own component, independent of
+        // the nftables-backed `BanManager` the echo server enforces with.
+        let gossip_banlist = Arc::new(BanList::with_defaults());
+
+        let gossip_bind = config.gossip.bind.clone();
+        let gossip_digest_interval = Duration::from_millis(config.gossip.digest_interval_ms);
+        let gossip_feed_hub = Arc::clone(&feed_hub);
+        let gossip_node_for_serve = Arc::clone(&gossip_node);
+        let gossip_banlist_for_serve = Arc::clone(&gossip_banlist);
+        tokio::spawn(async move {
+            if let Err(e) = gossip::serve(
+                &gossip_bind,
+                gossip_node_for_serve,
+                gossip_banlist_for_serve,
+                gossip_feed_hub,
+                gossip_digest_interval,
+            )
+            .await
+            {
+                error!("Gossip server error: {}", e);
+            }
+        });
+
+        // Same eviction treatment as the other ban stores, so gossip-learned
+        // bans and offense counters don't grow unbounded either.
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(30));
+            loop {
+                ticker.tick().await;
+                gossip_banlist.evict_expired();
+            }
+        });
+    }
+
+    // Start the real-time threat feed WebSocket server in background
+    tokio::spawn(async move {
+        if let Err(e) = rust_protocol::feed::serve("0.0.0.0:7880", feed_hub).await {
+            error!("Threat feed server error: {}", e);
+        }
+    });
+
+    // Keep systemd's STATUS= line current with connection counts.
+    let status_stats = Arc::clone(&stats);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(10));
+        loop {
+            ticker.tick().await;
+            let stats_json = status_stats.get_stats().await;
+            systemd::notify_status(&format!(
+                "active={} total={}",
+                stats_json["active_connections"], stats_json["total_connections"]
+            ));
+        }
+    });
+
+    // Periodically drop bans whose TTL has lapsed; otherwise the nftables
+    // set and the in-memory ban map both grow unbounded for a long-running
+    // daemon.
+    let expire_stats = Arc::clone(&stats);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(30));
+        loop {
+            ticker.tick().await;
+            expire_stats.banmgr.expire_stale();
+        }
+    });
+
+    // Pet the watchdog at half the configured interval, if systemd asked
+    // for one via WATCHDOG_USEC.
+    if let Some(interval) = systemd::watchdog_interval() {
+        info!("systemd watchdog requested every {:?}; petting at half that", interval);
+        systemd::spawn_watchdog(interval);
+    }
+
     // Main server loop
     loop {
         match listener.accept().await {
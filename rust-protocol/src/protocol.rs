@@ -2,7 +2,7 @@
 // -------------------------
 // Core protocol parsing and message handling functionality
 
-use crate::{ProtocolMessage, ThreatEvent, generate_fingerprint};
+use crate::{MessageType, ProtocolMessage, ThreatEvent, generate_fingerprint};
 use serde_json;
 use std::net::SocketAddr;
 use chrono::Utc;
@@ -12,32 +12,126 @@ use uuid::Uuid;
 pub fn parse_message(data: &[u8], source: SocketAddr) -> Result<ProtocolMessage, Box<dyn std::error::Error>> {
     let payload = String::from_utf8_lossy(data);
     let fingerprint = generate_fingerprint(data);
-    
+
     // Detect message type based on content patterns
     let message_type = detect_message_type(&payload);
-    
+    let protocol_version = extract_protocol_version(&payload, message_type);
+
     Ok(ProtocolMessage {
         id: Uuid::new_v4().to_string(),
         timestamp: Utc::now(),
         source,
         message_type,
+        protocol_version,
         payload: payload.to_string(),
         fingerprint: Some(fingerprint),
     })
 }
 
 /// Detect the type of protocol message based on content
-fn detect_message_type(payload: &str) -> String {
+fn detect_message_type(payload: &str) -> MessageType {
     if payload.contains("SSH-") {
-        "ssh_handshake".to_string()
+        MessageType::SshHandshake
     } else if payload.contains("HTTP/") {
-        "http_request".to_string()
+        MessageType::HttpRequest
     } else if payload.contains("FTP") {
-        "ftp_command".to_string()
+        MessageType::FtpCommand
     } else if payload.contains("SMTP") {
-        "smtp_command".to_string()
+        MessageType::SmtpCommand
     } else {
-        "unknown".to_string()
+        MessageType::Unknown
+    }
+}
+
+/// Pull the dialect version token out of a handshake/request line, e.g.
+/// the `2.0` in `SSH-2.0-OpenSSH_8.9p1` or the `1.1` in `HTTP/1.1`. FTP
+/// and SMTP commands don't carry a version in the command itself, so
+/// this only ever resolves for SSH/HTTP.
+fn extract_protocol_version(payload: &str, message_type: MessageType) -> Option<String> {
+    match message_type {
+        MessageType::SshHandshake => payload
+            .split("SSH-")
+            .nth(1)
+            .and_then(|rest| rest.split('-').next())
+            .map(|v| v.to_string()),
+        MessageType::HttpRequest => payload
+            .split("HTTP/")
+            .nth(1)
+            .and_then(|rest| rest.split_whitespace().next())
+            .map(|v| v.to_string()),
+        MessageType::FtpCommand | MessageType::SmtpCommand | MessageType::Unknown => None,
+    }
+}
+
+/// Version strings the honeypot advertises per protocol, kept in one
+/// place so the banners `process_data` returns stay internally
+/// consistent with whatever `extract_protocol_version` would parse back
+/// out of them. Swap these (e.g. via `config::BannerConfig`) to mimic a
+/// different deployed version without touching response logic.
+#[derive(Debug, Clone)]
+pub struct EmulatedVersions {
+    pub ssh: String,
+    pub http: String,
+    pub ftp: String,
+    pub smtp: String,
+}
+
+impl EmulatedVersions {
+    pub fn with_defaults() -> Self {
+        Self {
+            ssh: "SSH-2.0-OpenSSH_8.9p1".to_string(),
+            http: "HTTP/1.1".to_string(),
+            ftp: "220 FTP Server (vsftpd 3.0.3) ready.".to_string(),
+            smtp: "220 mail.example.com ESMTP Postfix".to_string(),
+        }
+    }
+
+    pub fn ssh_banner(&self) -> String {
+        format!("{}\r\n", self.ssh)
+    }
+
+    pub fn http_not_found(&self) -> String {
+        format!("{} 404 Not Found\r\nContent-Length: 0\r\n\r\n", self.http)
+    }
+
+    pub fn ftp_banner(&self) -> String {
+        format!("{}\r\n", self.ftp)
+    }
+
+    pub fn smtp_banner(&self) -> String {
+        format!("{}\r\n", self.smtp)
+    }
+}
+
+/// One compiled user-supplied detection rule from the config file,
+/// merged into the built-in pattern set below. Compilation (and
+/// fail-fast on a bad pattern) happens in `config::Config::compile_detection_rules`.
+pub struct CompiledRule {
+    pub name: String,
+    pub severity: String,
+    pub regex: Regex,
+}
+
+static CUSTOM_RULES: OnceLock<Vec<CompiledRule>> = OnceLock::new();
+
+/// Install the config-supplied detection rules so `analyze_for_threats`
+/// merges them with the built-in set. Intended to be called once at
+/// startup; later calls are ignored, matching the `OnceLock`-backed lazy
+/// statics used elsewhere in this crate.
+pub fn install_custom_rules(rules: Vec<CompiledRule>) {
+    let _ = CUSTOM_RULES.set(rules);
+}
+
+fn custom_rules() -> &'static [CompiledRule] {
+    CUSTOM_RULES.get().map(Vec::as_slice).unwrap_or(&[])
+}
+
+fn severity_rank(severity: &str) -> u8 {
+    match severity {
+        "critical" => 3,
+        "high" => 2,
+        "medium" => 1,
+        _ => 0,
     }
 }
 
@@ -90,6 +184,17 @@ fn data_exfiltration_regex() -> &'static Regex {
     CELL.get_or_init(|| Regex::new(r"(?i)(tar\s|zip\s|gzip|base64|xxd|hexdump|cat.*passwd|cat.*shadow)").unwrap())
 }
 
+/// Literal tool/technique names that name the attack outright, regardless
+/// of which protocol carries them. Kept separate from the more structural
+/// patterns above since these are just a denylist of known offensive
+/// tooling rather than a syntax shape.
+fn known_attack_tool_regex() -> &'static Regex {
+    static CELL: OnceLock<Regex> = OnceLock::new();
+    CELL.get_or_init(|| {
+        Regex::new(r"(?i)(shellcode|exploit|payload|metasploit|reverse_shell|bind_shell)").unwrap()
+    })
+}
+
 /// Convert a ProtocolMessage to a ThreatEvent if it contains suspicious patterns
 pub fn analyze_for_threats(message: &ProtocolMessage) -> Option<ThreatEvent> {
     // We don't need to lowercase manually as regexes are case-insensitive (?i)
@@ -100,89 +205,108 @@ pub fn analyze_for_threats(message: &ProtocolMessage) -> Option<ThreatEvent> {
     // Check for common attack patterns using Regex
     // The regex crate guarantees linear time execution (O(m * n)), preventing ReDoS
     
-    let mut threat_types = Vec::new();
-    let mut descriptions = Vec::new();
-    let mut max_severity = "low";
+    let mut threat_types: Vec<String> = Vec::new();
+    let mut descriptions: Vec<String> = Vec::new();
+    let mut max_severity = "low".to_string();
 
     // Critical severity threats
     if command_injection_regex().is_match(payload) {
-        threat_types.push("command_injection");
-        descriptions.push("Potential command injection attempt detected");
-        max_severity = "critical";
+        threat_types.push("command_injection".to_string());
+        descriptions.push("Potential command injection attempt detected".to_string());
+        max_severity = "critical".to_string();
     }
-    
+
     // High severity threats
     if directory_traversal_regex().is_match(payload) {
-        threat_types.push("directory_traversal");
-        descriptions.push("Directory traversal attack pattern");
-        if max_severity != "critical" { max_severity = "high"; }
+        threat_types.push("directory_traversal".to_string());
+        descriptions.push("Directory traversal attack pattern".to_string());
+        if max_severity != "critical" { max_severity = "high".to_string(); }
     }
-    
+
     if sql_injection_regex().is_match(payload) {
-        threat_types.push("sql_injection");
-        descriptions.push("SQL injection attempt detected");
-        if max_severity != "critical" { max_severity = "high"; }
+        threat_types.push("sql_injection".to_string());
+        descriptions.push("SQL injection attempt detected".to_string());
+        if max_severity != "critical" { max_severity = "high".to_string(); }
     }
-    
+
     if privilege_escalation_regex().is_match(payload) {
-        threat_types.push("privilege_escalation");
-        descriptions.push("Privilege escalation attempt");
-        if max_severity != "critical" { max_severity = "high"; }
+        threat_types.push("privilege_escalation".to_string());
+        descriptions.push("Privilege escalation attempt".to_string());
+        if max_severity != "critical" { max_severity = "high".to_string(); }
     }
-    
+
+    if known_attack_tool_regex().is_match(payload) {
+        threat_types.push("known_attack_tool".to_string());
+        descriptions.push("Payload references known offensive tooling".to_string());
+        if max_severity != "critical" { max_severity = "high".to_string(); }
+    }
+
     // Medium severity threats
     if xss_regex().is_match(payload) {
-        threat_types.push("xss_attempt");
-        descriptions.push("XSS attack pattern detected");
-        if max_severity == "low" { max_severity = "medium"; }
+        threat_types.push("xss_attempt".to_string());
+        descriptions.push("XSS attack pattern detected".to_string());
+        if max_severity == "low" { max_severity = "medium".to_string(); }
     }
-    
+
     if lateral_movement_regex().is_match(payload) {
-        threat_types.push("lateral_movement");
-        descriptions.push("Lateral movement technique detected");
-        if max_severity == "low" { max_severity = "medium"; }
+        threat_types.push("lateral_movement".to_string());
+        descriptions.push("Lateral movement technique detected".to_string());
+        if max_severity == "low" { max_severity = "medium".to_string(); }
     }
-    
+
     if persistence_regex().is_match(payload) {
-        threat_types.push("persistence");
-        descriptions.push("Persistence mechanism detected");
-        if max_severity == "low" { max_severity = "medium"; }
+        threat_types.push("persistence".to_string());
+        descriptions.push("Persistence mechanism detected".to_string());
+        if max_severity == "low" { max_severity = "medium".to_string(); }
     }
-    
+
     if data_exfiltration_regex().is_match(payload) {
-        threat_types.push("data_exfiltration");
-        descriptions.push("Data exfiltration attempt");
-        if max_severity == "low" { max_severity = "medium"; }
+        threat_types.push("data_exfiltration".to_string());
+        descriptions.push("Data exfiltration attempt".to_string());
+        if max_severity == "low" { max_severity = "medium".to_string(); }
     }
-    
-    // Low severity - reconnaissance 
+
+    // Low severity - reconnaissance
     if reconnaissance_regex().is_match(payload) {
-        threat_types.push("reconnaissance");
-        descriptions.push("Reconnaissance activity detected");
+        threat_types.push("reconnaissance".to_string());
+        descriptions.push("Reconnaissance activity detected".to_string());
         // Keep as low severity unless higher severity already detected
     }
-    
+
+    // User-supplied rules from the config file, merged in alongside the
+    // built-in set.
+    for rule in custom_rules() {
+        if rule.regex.is_match(payload) {
+            threat_types.push(rule.name.clone());
+            descriptions.push(format!("Custom rule '{}' matched", rule.name));
+            if severity_rank(&rule.severity) > severity_rank(&max_severity) {
+                max_severity = rule.severity.clone();
+            }
+        }
+    }
+
     if !threat_types.is_empty() {
         let combined_description = descriptions.join(", ");
-        let primary_threat = threat_types[0]; // Use first detected threat as primary
-        
+        let primary_threat = threat_types[0].clone(); // Use first detected threat as primary
+
         return Some(ThreatEvent {
             event_id: Uuid::new_v4().to_string(),
             timestamp: Utc::now(),
             source_ip: message.source.ip().to_string(),
-            event_type: primary_threat.to_string(),
+            event_type: primary_threat,
             description: combined_description,
-            severity: max_severity.to_string(),
+            severity: max_severity,
             metadata: serde_json::json!({
                 "message_id": message.id,
                 "message_type": message.message_type,
+                "protocol_version": message.protocol_version,
                 "fingerprint": message.fingerprint,
                 "all_threats": threat_types,
                 "threat_count": threat_types.len()
             }),
         });
     }
-    
+
     None
 }
 
@@ -206,7 +330,8 @@ mod tests {
             id: "test".to_string(),
             timestamp: Utc::now(),
             source: "127.0.0.1:1234".parse().unwrap(),
-            message_type: "http".to_string(),
+            message_type: MessageType::HttpRequest,
+            protocol_version: None,
             payload: "user=admin' UNION SELECT 1,2,3--".to_string(),
             fingerprint: None,
         };
@@ -222,7 +347,8 @@ mod tests {
             id: "test".to_string(),
             timestamp: Utc::now(),
             source: "127.0.0.1:1234".parse().unwrap(),
-            message_type: "http".to_string(),
+            message_type: MessageType::HttpRequest,
+            protocol_version: None,
             payload: "GET /../../etc/passwd HTTP/1.1".to_string(),
             fingerprint: None,
         };
@@ -238,7 +364,8 @@ mod tests {
             id: "test".to_string(),
             timestamp: Utc::now(),
             source: "127.0.0.1:1234".parse().unwrap(),
-            message_type: "http".to_string(),
+            message_type: MessageType::HttpRequest,
+            protocol_version: None,
             payload: "<script>alert(1)</script>".to_string(),
             fingerprint: None,
         };
@@ -248,13 +375,31 @@ mod tests {
         assert_eq!(threat.unwrap().event_type, "xss_attempt");
     }
 
+    #[test]
+    fn test_known_attack_tool_detection() {
+        let msg = ProtocolMessage {
+            id: "test".to_string(),
+            timestamp: Utc::now(),
+            source: "127.0.0.1:1234".parse().unwrap(),
+            message_type: MessageType::Unknown,
+            protocol_version: None,
+            payload: "downloading metasploit payload now".to_string(),
+            fingerprint: None,
+        };
+
+        let threat = analyze_for_threats(&msg).unwrap();
+        assert_eq!(threat.event_type, "known_attack_tool");
+        assert_eq!(threat.severity, "high");
+    }
+
     #[test]
     fn test_command_injection_detection() {
         let msg = ProtocolMessage {
             id: "test".to_string(),
             timestamp: Utc::now(),
             source: "127.0.0.1:1234".parse().unwrap(),
-            message_type: "ssh".to_string(),
+            message_type: MessageType::SshHandshake,
+            protocol_version: None,
             payload: "cat file; rm -rf /".to_string(),
             fingerprint: None,
         };
@@ -270,7 +415,8 @@ mod tests {
             id: "test".to_string(),
             timestamp: Utc::now(),
             source: "127.0.0.1:1234".parse().unwrap(),
-            message_type: "http".to_string(),
+            message_type: MessageType::HttpRequest,
+            protocol_version: None,
             payload: "GET /index.html HTTP/1.1".to_string(),
             fingerprint: None,
         };
@@ -278,4 +424,66 @@ mod tests {
         let threat = analyze_for_threats(&msg);
         assert!(threat.is_none());
     }
+
+    #[test]
+    fn test_parse_message_extracts_ssh_version() {
+        let source = "127.0.0.1:4444".parse().unwrap();
+        let msg = parse_message(b"SSH-2.0-OpenSSH_8.9p1\r\n", source).unwrap();
+        assert_eq!(msg.message_type, MessageType::SshHandshake);
+        assert_eq!(msg.protocol_version.as_deref(), Some("2.0"));
+    }
+
+    #[test]
+    fn test_parse_message_extracts_http_version() {
+        let source = "127.0.0.1:4444".parse().unwrap();
+        let msg = parse_message(b"GET / HTTP/1.1\r\nHost: example.com\r\n", source).unwrap();
+        assert_eq!(msg.message_type, MessageType::HttpRequest);
+        assert_eq!(msg.protocol_version.as_deref(), Some("1.1"));
+    }
+
+    #[test]
+    fn test_parse_message_unknown_has_no_version() {
+        let source = "127.0.0.1:4444".parse().unwrap();
+        let msg = parse_message(b"garbage probe", source).unwrap();
+        assert_eq!(msg.message_type, MessageType::Unknown);
+        assert_eq!(msg.protocol_version, None);
+    }
+
+    #[test]
+    fn test_message_type_accepts_legacy_short_form_on_deserialize() {
+        let legacy: MessageType = serde_json::from_str("\"ssh\"").unwrap();
+        assert_eq!(legacy, MessageType::SshHandshake);
+
+        let canonical: MessageType = serde_json::from_str("\"ssh_handshake\"").unwrap();
+        assert_eq!(canonical, MessageType::SshHandshake);
+    }
+
+    #[test]
+    fn test_message_type_unrecognized_string_falls_back_to_unknown() {
+        let parsed: MessageType = serde_json::from_str("\"carrier_pigeon\"").unwrap();
+        assert_eq!(parsed, MessageType::Unknown);
+    }
+
+    #[test]
+    fn test_custom_rule_is_merged_into_builtin_detection() {
+        install_custom_rules(vec![CompiledRule {
+            name: "custom_scanner_ua".to_string(),
+            severity: "high".to_string(),
+            regex: Regex::new(r"(?i)nikto").unwrap(),
+        }]);
+
+        let msg = ProtocolMessage {
+            id: "test".to_string(),
+            timestamp: Utc::now(),
+            source: "127.0.0.1:1234".parse().unwrap(),
+            message_type: MessageType::HttpRequest,
+            protocol_version: None,
+            payload: "User-Agent: Nikto/2.5.0".to_string(),
+            fingerprint: None,
+        };
+
+        let threat = analyze_for_threats(&msg).expect("custom rule should fire");
+        assert_eq!(threat.event_type, "custom_scanner_ua");
+        assert_eq!(threat.severity, "high");
+    }
 }
@@ -18,7 +18,7 @@
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use aho_corasick::AhoCorasick;
 use bloom::{BloomFilter, ASMS};
 
@@ -59,6 +59,7 @@ pub mod tags {
     pub const BURSTY: u32 = 1 << 3;
     pub const ODD_CADENCE: u32 = 1 << 4;
     pub const PROTO_UNKNOWN: u32 = 1 << 5;
+    pub const SYNTHETIC_STACK: u32 = 1 << 6; // stack signature inconsistent with claimed protocol
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -76,6 +77,7 @@ pub struct Layer0Output {
     pub tags: u32,              // Bitflags from tags::*
     pub escalate: bool,
     pub suspicion_score: u8,    // 0-255 additive score
+    pub os_guess: Option<&'static str>, // passive stack fingerprint guess, if any
 }
 
 impl Layer0Output {
@@ -86,6 +88,7 @@ impl Layer0Output {
             tags: 0,
             escalate: false,
             suspicion_score: 0,
+            os_guess: None,
         }
     }
 
@@ -108,6 +111,9 @@ pub enum Protocol {
     HTTP,
     FTP,
     SMTP,
+    HTTP2,
+    Redis,
+    Telnet,
     Unknown,
 }
 
@@ -118,18 +124,67 @@ impl Protocol {
             Protocol::HTTP => "http",
             Protocol::FTP => "ftp",
             Protocol::SMTP => "smtp",
+            Protocol::HTTP2 => "http2",
+            Protocol::Redis => "redis",
+            Protocol::Telnet => "telnet",
             Protocol::Unknown => "unknown",
         }
     }
 }
 
-/// Classify protocol from first bytes (no regex, pure byte prefix)
+/// The h2c (HTTP/2 cleartext) connection preface. A real client opens
+/// with this before its first SETTINGS frame.
+const H2C_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+/// Case-insensitive substring search — still "no regex", just a
+/// byte-window scan over the whole haystack; callers bound its length.
+fn contains_ascii_ci(haystack: &[u8], needle: &[u8]) -> bool {
+    if needle.is_empty() || haystack.len() < needle.len() {
+        return false;
+    }
+    haystack.windows(needle.len()).any(|w| w.eq_ignore_ascii_case(needle))
+}
+
+/// An HTTP/1.1 request asking to upgrade to h2c carries `Upgrade: h2c`.
+/// Only the first couple KB are scanned — enough for request headers.
+fn looks_like_h2c_upgrade(data: &[u8]) -> bool {
+    let haystack = &data[..data.len().min(2048)];
+    contains_ascii_ci(haystack, b"upgrade: h2c")
+}
+
+/// Redis RESP probe, e.g. `*1\r\n$4\r\nPING`: a `*`-prefixed array
+/// header followed by digits and a CRLF. Inline commands such as a bare
+/// `PING\r\n` are not matched.
+fn is_redis_resp_prefix(data: &[u8]) -> bool {
+    if data.len() < 4 || data[0] != b'*' || !data[1].is_ascii_digit() {
+        return false;
+    }
+    data.windows(2).take(8).any(|w| w == b"\r\n")
+}
+
+/// Classify protocol from first bytes (no regex, pure byte prefix plus a
+/// couple of small bounded scans for h2c upgrade headers)
 /// CONSTRAINT: Misclassification must fail boringly (dead socket, timeout, malformed banner)
 pub fn classify_protocol_fast(data: &[u8]) -> Protocol {
     if data.is_empty() {
         return Protocol::Unknown;
     }
 
+    // HTTP/2 cleartext (h2c) connection preface
+    if data.len() >= H2C_PREFACE.len() && &data[..H2C_PREFACE.len()] == H2C_PREFACE {
+        return Protocol::HTTP2;
+    }
+
+    // Telnet IAC option negotiation: 0xFF followed by WILL/WONT/DO/DONT
+    if data.len() >= 2 && data[0] == 0xFF && matches!(data[1], 0xFB..=0xFE) {
+        return Protocol::Telnet;
+    }
+
+    // Redis RESP array header
+    if is_redis_resp_prefix(data) {
+        return Protocol::Redis;
+    }
+
     // SSH: starts with "SSH-"
     if data.len() >= 4 && &data[0..4] == b"SSH-" {
         return Protocol::SSH;
@@ -139,6 +194,9 @@ pub fn classify_protocol_fast(data: &[u8]) -> Protocol {
     if data.len() >= 3 {
         let prefix = &data[0..3];
         if prefix == b"GET" || prefix == b"POS" || prefix == b"PUT" || prefix == b"DEL" || prefix == b"HEA" || prefix == b"OPT" {
+            if looks_like_h2c_upgrade(data) {
+                return Protocol::HTTP2;
+            }
             return Protocol::HTTP;
         }
     }
@@ -162,6 +220,21 @@ pub fn classify_protocol_fast(data: &[u8]) -> Protocol {
     Protocol::Unknown
 }
 
+/// A well-formed empty SETTINGS frame followed by a GOAWAY(PROTOCOL_ERROR)
+/// frame on stream 0 — a real HTTP/2 server closing a connection it
+/// doesn't like, so the liar stays consistent at the frame level instead
+/// of dumping a plain HTTP/1 "400" on an h2c client.
+const HTTP2_SETTINGS_THEN_GOAWAY: &[u8] = &[
+    // SETTINGS, length=0, type=0x4, flags=0x0, stream=0
+    0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00,
+    // GOAWAY, length=8, type=0x7, flags=0x0, stream=0
+    0x00, 0x00, 0x08, 0x07, 0x00, 0x00, 0x00, 0x00, 0x00,
+    // last_stream_id = 0
+    0x00, 0x00, 0x00, 0x00,
+    // error_code = 1 (PROTOCOL_ERROR)
+    0x00, 0x00, 0x00, 0x01,
+];
+
 /// Get boring failure response for misclassified protocol
 pub fn boring_failure_response(expected: Protocol) -> &'static [u8] {
     match expected {
@@ -169,6 +242,9 @@ pub fn boring_failure_response(expected: Protocol) -> &'static [u8] {
         Protocol::HTTP => b"HTTP/1.0 400 Bad Request\r\n\r\n",
         Protocol::FTP => b"500 Syntax error, command unrecognized.\r\n",
         Protocol::SMTP => b"500 Syntax error, command unrecognized\r\n",
+        Protocol::HTTP2 => HTTP2_SETTINGS_THEN_GOAWAY,
+        Protocol::Redis => b"-ERR unknown command\r\n",
+        Protocol::Telnet => b"", // dead socket
         Protocol::Unknown => b"",
     }
 }
@@ -253,21 +329,157 @@ pub enum Verdict {
     KnownNoise,   // Scanner/mass exploit
 }
 
+/// Number of independent shards each sharded structure below splits into.
+/// Each shard gets its own `Mutex`, so concurrent connections hashing to
+/// different shards never serialize behind one lock.
+const SHARD_COUNT: usize = 16;
+
+fn shard_index(hash: u64) -> usize {
+    (hash as usize) % SHARD_COUNT
+}
+
+/// One slot in the intrusive doubly-linked LRU list. `prev`/`next` are
+/// slab indices, not pointers, so the whole shard is self-contained and
+/// `Send`.
+struct LruNode<V> {
+    key: u64,
+    value: V,
+    inserted_at: u64,
+    prev: Option<usize>,
+    next: Option<usize>,
+}
+
+/// A single LRU shard: a slab of nodes plus a `HashMap<key, slab index>`
+/// so `get`/`set` are O(1) and touching a key promotes it to the head
+/// without scanning, unlike the old `min_by_key` eviction scan.
+struct LruShard<V> {
+    slab: Vec<LruNode<V>>,
+    free: Vec<usize>,
+    index: HashMap<u64, usize>,
+    head: Option<usize>, // most recently used
+    tail: Option<usize>, // least recently used
+    capacity: usize,
+}
+
+impl<V: Copy> LruShard<V> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            slab: Vec::new(),
+            free: Vec::new(),
+            index: HashMap::new(),
+            head: None,
+            tail: None,
+            capacity: capacity.max(1),
+        }
+    }
+
+    fn unlink(&mut self, idx: usize) {
+        let (prev, next) = (self.slab[idx].prev, self.slab[idx].next);
+
+        match prev {
+            Some(p) => self.slab[p].next = next,
+            None => self.head = next,
+        }
+        match next {
+            Some(n) => self.slab[n].prev = prev,
+            None => self.tail = prev,
+        }
+    }
+
+    fn push_front(&mut self, idx: usize) {
+        self.slab[idx].prev = None;
+        self.slab[idx].next = self.head;
+        if let Some(old_head) = self.head {
+            self.slab[old_head].prev = Some(idx);
+        }
+        self.head = Some(idx);
+        if self.tail.is_none() {
+            self.tail = Some(idx);
+        }
+    }
+
+    fn touch(&mut self, idx: usize) {
+        if self.head == Some(idx) {
+            return;
+        }
+        self.unlink(idx);
+        self.push_front(idx);
+    }
+
+    fn evict_tail(&mut self) {
+        if let Some(tail_idx) = self.tail {
+            self.unlink(tail_idx);
+            self.index.remove(&self.slab[tail_idx].key);
+            self.free.push(tail_idx);
+        }
+    }
+
+    fn get(&mut self, key: u64, now: u64, ttl_ms: u64) -> Option<V> {
+        let idx = *self.index.get(&key)?;
+
+        if now.saturating_sub(self.slab[idx].inserted_at) >= ttl_ms {
+            self.unlink(idx);
+            self.index.remove(&key);
+            self.free.push(idx);
+            return None;
+        }
+
+        self.touch(idx);
+        Some(self.slab[idx].value)
+    }
+
+    fn set(&mut self, key: u64, value: V, now: u64) {
+        if let Some(&idx) = self.index.get(&key) {
+            self.slab[idx].value = value;
+            self.slab[idx].inserted_at = now;
+            self.touch(idx);
+            return;
+        }
+
+        if self.index.len() >= self.capacity {
+            self.evict_tail();
+        }
+
+        let node = LruNode {
+            key,
+            value,
+            inserted_at: now,
+            prev: None,
+            next: None,
+        };
+
+        let idx = if let Some(free_idx) = self.free.pop() {
+            self.slab[free_idx] = node;
+            free_idx
+        } else {
+            self.slab.push(node);
+            self.slab.len() - 1
+        };
+
+        self.index.insert(key, idx);
+        self.push_front(idx);
+    }
+}
+
 /// Cache for verdict metadata only
 /// CONSTRAINT: Cache verdicts, not responses (prevents determinism fingerprinting)
+///
+/// Sharded into `SHARD_COUNT` independent LRUs so a scanner flood hammering
+/// the cache doesn't serialize every connection through one lock, and
+/// eviction is O(1) per shard instead of an O(n) `min_by_key` scan.
 pub struct VerdictCache {
-    cache: Arc<Mutex<HashMap<u64, (Verdict, u64)>>>, // (key) -> (verdict, timestamp)
-    max_size: usize,
+    shards: Vec<Mutex<LruShard<Verdict>>>,
     ttl_ms: u64,
 }
 
 impl VerdictCache {
     pub fn new(max_size: usize, ttl_ms: u64) -> Self {
-        Self {
-            cache: Arc::new(Mutex::new(HashMap::new())),
-            max_size,
-            ttl_ms,
-        }
+        let per_shard_capacity = (max_size / SHARD_COUNT).max(1);
+        let shards = (0..SHARD_COUNT)
+            .map(|_| Mutex::new(LruShard::new(per_shard_capacity)))
+            .collect();
+
+        Self { shards, ttl_ms }
     }
 
     /// Generate cache key from IP and payload fingerprint
@@ -284,32 +496,16 @@ impl VerdictCache {
     /// Check cached verdict
     pub fn get(&self, key: u64) -> Option<Verdict> {
         let now = current_time_ms();
-        let mut cache = self.cache.lock().unwrap();
-
-        if let Some((verdict, timestamp)) = cache.get(&key) {
-            if now - timestamp < self.ttl_ms {
-                return Some(*verdict);
-            } else {
-                cache.remove(&key);
-            }
-        }
-
-        None
+        let mut shard = self.shards[shard_index(key)].lock().unwrap();
+        shard.get(key, now, self.ttl_ms)
     }
 
-    /// Store verdict (evict oldest if at capacity)
+    /// Store verdict (evicts the shard's least-recently-used entry if at
+    /// per-shard capacity)
     pub fn set(&self, key: u64, verdict: Verdict) {
         let now = current_time_ms();
-        let mut cache = self.cache.lock().unwrap();
-
-        // Evict oldest if at capacity
-        if cache.len() >= self.max_size {
-            if let Some(oldest_key) = cache.iter().min_by_key(|(_, (_, ts))| ts).map(|(k, _)| *k) {
-                cache.remove(&oldest_key);
-            }
-        }
-
-        cache.insert(key, (verdict, now));
+        let mut shard = self.shards[shard_index(key)].lock().unwrap();
+        shard.set(key, verdict, now);
     }
 }
 
@@ -429,24 +625,38 @@ impl RateStats {
     }
 }
 
-/// Global per-IP rate tracking
+fn hash_ip(ip: &str) -> u64 {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    ip.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Global per-IP rate tracking, sharded into `SHARD_COUNT` independent
+/// maps (one `Mutex` each) so a flood of distinct source IPs spreads
+/// across locks instead of serializing on one global map.
 pub struct RateTracker {
-    stats: Arc<Mutex<HashMap<String, Arc<RateStats>>>>,
+    shards: Vec<Mutex<HashMap<String, Arc<RateStats>>>>,
     window_size: usize,
 }
 
 impl RateTracker {
     pub fn new(window_size: usize) -> Self {
-        Self {
-            stats: Arc::new(Mutex::new(HashMap::new())),
-            window_size,
-        }
+        let shards = (0..SHARD_COUNT).map(|_| Mutex::new(HashMap::new())).collect();
+        Self { shards, window_size }
+    }
+
+    fn shard_for(&self, ip: &str) -> &Mutex<HashMap<String, Arc<RateStats>>> {
+        &self.shards[shard_index(hash_ip(ip))]
     }
 
     /// Get or create stats for an IP
     pub fn get_stats(&self, ip: &str) -> Arc<RateStats> {
-        let mut stats = self.stats.lock().unwrap();
-        stats.entry(ip.to_string())
+        let mut shard = self.shard_for(ip).lock().unwrap();
+        shard
+            .entry(ip.to_string())
             .or_insert_with(|| Arc::new(RateStats::new(self.window_size)))
             .clone()
     }
@@ -457,18 +667,22 @@ impl RateTracker {
         stats.record();
     }
 
-    /// Clean up old entries (periodic maintenance)
+    /// Clean up old entries (periodic maintenance). Each shard is locked
+    /// and scanned independently, so this no longer blocks `get_stats`/
+    /// `record` calls landing on other shards while it runs.
     pub fn cleanup_inactive(&self, max_age_ms: u64) {
         let now = current_time_ms();
-        let mut stats = self.stats.lock().unwrap();
-        
-        stats.retain(|_, rate_stats| {
-            // Keep if any timestamp is recent
-            rate_stats.requests.iter().any(|ts| {
-                let t = ts.load(Ordering::Relaxed);
-                t > 0 && now - t < max_age_ms
-            })
-        });
+
+        for shard in &self.shards {
+            let mut shard = shard.lock().unwrap();
+            shard.retain(|_, rate_stats| {
+                // Keep if any timestamp is recent
+                rate_stats.requests.iter().any(|ts| {
+                    let t = ts.load(Ordering::Relaxed);
+                    t > 0 && now - t < max_age_ms
+                })
+            });
+        }
     }
 }
 
@@ -657,6 +871,275 @@ pub fn route_payload(
     ResponseProfile::FastFake
 }
 
+// ============================================================================
+// 8. STREAMING CLASSIFIER (Drip-Fed Probes)
+// ============================================================================
+
+/// Hard cap on the accumulation buffer. Once a connection has sent this
+/// many bytes without the prefix matchers or noise detector reaching a
+/// verdict, we fail boringly rather than buffer forever.
+const STREAMING_BUFFER_CAP: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClassifyState {
+    NeedMore,
+    Decided(Protocol),
+    GiveUp,
+}
+
+/// Classifies a connection's protocol across multiple reads, for
+/// slow-loris clients, fragmented TLS records, or banners split across
+/// packets that a single-shot `classify_protocol_fast` call would miss.
+pub struct StreamingClassifier {
+    buffer: Vec<u8>,
+    detector: NoiseDetector,
+    last_chunk_at: Option<u64>,
+    inter_arrival_ms: Vec<u64>,
+    tags: u32,
+    gave_up: bool,
+}
+
+impl StreamingClassifier {
+    pub fn new() -> Self {
+        Self {
+            buffer: Vec::with_capacity(STREAMING_BUFFER_CAP),
+            detector: NoiseDetector::new(),
+            last_chunk_at: None,
+            inter_arrival_ms: Vec::new(),
+            tags: 0,
+            gave_up: false,
+        }
+    }
+
+    /// Tags accumulated from fragmentation behavior and noise hints so
+    /// far (`tags::ODD_CADENCE`, `tags::BURSTY`, `tags::EXPLOIT_HINT`).
+    pub fn tags(&self) -> u32 {
+        self.tags
+    }
+
+    fn record_cadence(&mut self) {
+        let now = current_time_ms();
+        if let Some(last) = self.last_chunk_at {
+            self.inter_arrival_ms.push(now.saturating_sub(last));
+        }
+        self.last_chunk_at = Some(now);
+
+        if self.inter_arrival_ms.len() < 2 {
+            return;
+        }
+
+        let deltas = &self.inter_arrival_ms;
+        let mean = deltas.iter().sum::<u64>() as f64 / deltas.len() as f64;
+        let variance = deltas.iter().map(|&d| (d as f64 - mean).powi(2)).sum::<f64>() / deltas.len() as f64;
+        let cv = if mean > 0.0 { variance.sqrt() / mean } else { 0.0 };
+
+        // High variance inter-arrival times: a human or deliberately
+        // irregular drip-feed.
+        if cv > 0.8 {
+            self.tags |= tags::ODD_CADENCE;
+        }
+        // Many small chunks arriving in rapid succession: classic
+        // slow-loris / fragmentation probing.
+        if mean < 50.0 && deltas.len() >= 3 {
+            self.tags |= tags::BURSTY;
+        }
+    }
+
+    /// Feed the next chunk read off the socket. Reruns the prefix
+    /// matchers and `NoiseDetector::check_hint` over the growing buffer,
+    /// and updates cadence tags from inter-chunk timing.
+    pub fn feed(&mut self, chunk: &[u8]) -> ClassifyState {
+        if self.gave_up {
+            return ClassifyState::GiveUp;
+        }
+
+        self.record_cadence();
+
+        let remaining = STREAMING_BUFFER_CAP.saturating_sub(self.buffer.len());
+        let take = chunk.len().min(remaining);
+        self.buffer.extend_from_slice(&chunk[..take]);
+
+        if self.detector.check_hint(&self.buffer).is_some() {
+            self.tags |= tags::EXPLOIT_HINT;
+        }
+
+        let proto = classify_protocol_fast(&self.buffer);
+        if proto != Protocol::Unknown {
+            return ClassifyState::Decided(proto);
+        }
+
+        if self.buffer.len() >= STREAMING_BUFFER_CAP {
+            self.gave_up = true;
+            return ClassifyState::GiveUp;
+        }
+
+        ClassifyState::NeedMore
+    }
+}
+
+impl Default for StreamingClassifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ============================================================================
+// 9. PLUGGABLE DETECTOR REGISTRY
+// ============================================================================
+
+/// A bounded, side-effect-free Layer 0 detector hook. Modules may only add
+/// tags and score to the shared output — never drop a request, never
+/// relax an existing suspicion score.
+pub trait Layer0Module: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn observe(&self, proto: Protocol, payload: &[u8], out: &mut Layer0Output);
+}
+
+/// Built-in module wrapping the existing `NoiseDetector` so default
+/// behavior is unchanged after the move to a registry.
+pub struct NoiseModule {
+    detector: NoiseDetector,
+}
+
+impl NoiseModule {
+    pub fn new() -> Self {
+        Self { detector: NoiseDetector::new() }
+    }
+}
+
+impl Default for NoiseModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Layer0Module for NoiseModule {
+    fn name(&self) -> &'static str {
+        "noise"
+    }
+
+    fn observe(&self, _proto: Protocol, payload: &[u8], out: &mut Layer0Output) {
+        if self.detector.check_hint(payload).is_some() {
+            out.add_tag(tags::EXPLOIT_HINT);
+            out.add_score(40);
+        }
+    }
+}
+
+/// Built-in module wrapping per-connection `RateStats`, bound to a single
+/// IP at construction time so `observe` stays payload-only like the trait
+/// requires.
+pub struct RateModule {
+    stats: Arc<RateStats>,
+}
+
+impl RateModule {
+    pub fn new(stats: Arc<RateStats>) -> Self {
+        Self { stats }
+    }
+}
+
+impl Layer0Module for RateModule {
+    fn name(&self) -> &'static str {
+        "rate"
+    }
+
+    fn observe(&self, _proto: Protocol, _payload: &[u8], out: &mut Layer0Output) {
+        self.stats.record();
+        match self.stats.rate_state() {
+            RateState::Insane => {
+                out.add_tag(tags::BURSTY);
+                out.add_score(25);
+            }
+            RateState::Bursty => {
+                out.add_tag(tags::BURSTY);
+                out.add_score(10);
+            }
+            RateState::Normal => {}
+        }
+    }
+}
+
+/// Built-in module wrapping `ScannerNoiseFilter`, bound to a single IP at
+/// construction time for the same reason as `RateModule`.
+pub struct BloomModule {
+    filter: Arc<ScannerNoiseFilter>,
+    ip: String,
+}
+
+impl BloomModule {
+    pub fn new(filter: Arc<ScannerNoiseFilter>, ip: String) -> Self {
+        Self { filter, ip }
+    }
+}
+
+impl Layer0Module for BloomModule {
+    fn name(&self) -> &'static str {
+        "bloom"
+    }
+
+    fn observe(&self, _proto: Protocol, payload: &[u8], out: &mut Layer0Output) {
+        if self.filter.is_probable_noise(&self.ip, payload) {
+            out.add_tag(tags::PROBABLE_NOISE);
+            out.add_score(5);
+        }
+        self.filter.mark_noise(&self.ip, payload);
+    }
+}
+
+/// Layer 0 iterates registered modules in a fixed (registration) order per
+/// request, under a compute budget so a slow third-party module gets
+/// work-shed instead of stalling the lane. Measured latency feeds back
+/// into the `AdaptiveCircuitBreaker` driving that work-shedding decision.
+pub struct ModuleRegistry {
+    modules: Vec<Box<dyn Layer0Module>>,
+}
+
+impl ModuleRegistry {
+    pub fn new() -> Self {
+        Self { modules: Vec::new() }
+    }
+
+    pub fn register(&mut self, module: Box<dyn Layer0Module>) -> &mut Self {
+        self.modules.push(module);
+        self
+    }
+
+    /// Run every registered module against one payload, stopping early
+    /// (work-shedding the remaining modules for this request only) once
+    /// `budget_ms` is exceeded or the breaker says to skip optional work.
+    pub fn observe_all(
+        &self,
+        proto: Protocol,
+        payload: &[u8],
+        out: &mut Layer0Output,
+        breaker: &AdaptiveCircuitBreaker,
+        budget_ms: u64,
+    ) {
+        let start = Instant::now();
+
+        for module in &self.modules {
+            if breaker.should_skip_optional() {
+                break;
+            }
+
+            module.observe(proto, payload, out);
+
+            if start.elapsed().as_millis() as u64 >= budget_ms {
+                break;
+            }
+        }
+
+        breaker.record_latency(start.elapsed().as_millis() as u64);
+    }
+}
+
+impl Default for ModuleRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -670,6 +1153,44 @@ mod tests {
         assert_eq!(classify_protocol_fast(b"random data"), Protocol::Unknown);
     }
 
+    #[test]
+    fn test_protocol_classification_h2c_preface() {
+        let data = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n\x00\x00\x00\x04\x00\x00\x00\x00\x00";
+        assert_eq!(classify_protocol_fast(data), Protocol::HTTP2);
+    }
+
+    #[test]
+    fn test_protocol_classification_h2c_upgrade_header() {
+        let data = b"GET / HTTP/1.1\r\nHost: x\r\nConnection: Upgrade\r\nUpgrade: h2c\r\n\r\n";
+        assert_eq!(classify_protocol_fast(data), Protocol::HTTP2);
+    }
+
+    #[test]
+    fn test_protocol_classification_redis_resp() {
+        assert_eq!(
+            classify_protocol_fast(b"*1\r\n$4\r\nPING\r\n"),
+            Protocol::Redis
+        );
+    }
+
+    #[test]
+    fn test_protocol_classification_telnet_iac() {
+        assert_eq!(classify_protocol_fast(&[0xFF, 0xFD, 0x01]), Protocol::Telnet);
+    }
+
+    #[test]
+    fn test_boring_failure_response_for_new_protocols() {
+        assert_eq!(
+            boring_failure_response(Protocol::HTTP2),
+            HTTP2_SETTINGS_THEN_GOAWAY
+        );
+        assert_eq!(
+            boring_failure_response(Protocol::Redis),
+            b"-ERR unknown command\r\n".as_slice()
+        );
+        assert_eq!(boring_failure_response(Protocol::Telnet), b"".as_slice());
+    }
+
     #[test]
     fn test_noise_detector_no_drop() {
         let detector = NoiseDetector::new();
@@ -688,6 +1209,53 @@ mod tests {
         assert_eq!(cache.get(key), Some(Verdict::Boring));
     }
 
+    #[test]
+    fn test_verdict_cache_evicts_lru_within_shard() {
+        // Use a capacity so small that one shard holds only a couple of
+        // entries.
+        let cache = VerdictCache::new(SHARD_COUNT * 2, 60_000);
+
+        // Hand-pick keys that land in the same shard so we can observe
+        // that shard's LRU eviction in isolation.
+        let shard_keys: Vec<u64> = (0u64..10_000)
+            .filter(|k| shard_index(*k) == 0)
+            .take(3)
+            .collect();
+        assert_eq!(shard_keys.len(), 3, "expected to find 3 keys in shard 0");
+
+        cache.set(shard_keys[0], Verdict::Boring);
+        cache.set(shard_keys[1], Verdict::NeedsL1);
+        // Touch the first key so it's no longer the least-recently-used.
+        assert_eq!(cache.get(shard_keys[0]), Some(Verdict::Boring));
+        // Per-shard capacity is max_size / SHARD_COUNT == 2, so inserting a
+        // third key must evict the LRU entry (shard_keys[1]).
+        cache.set(shard_keys[2], Verdict::KnownNoise);
+
+        assert_eq!(cache.get(shard_keys[0]), Some(Verdict::Boring));
+        assert_eq!(cache.get(shard_keys[1]), None);
+        assert_eq!(cache.get(shard_keys[2]), Some(Verdict::KnownNoise));
+    }
+
+    #[test]
+    fn test_verdict_cache_expires_by_ttl() {
+        let cache = VerdictCache::new(100, 0);
+        let key = VerdictCache::cache_key("10.0.0.1", b"probe");
+
+        cache.set(key, Verdict::NeedsL1);
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        assert_eq!(cache.get(key), None);
+    }
+
+    #[test]
+    fn test_rate_tracker_shards_independently() {
+        let tracker = RateTracker::new(16);
+        tracker.record("1.1.1.1");
+        tracker.record("2.2.2.2");
+
+        assert!(tracker.get_stats("1.1.1.1").requests_per_second() >= 1.0);
+        assert!(tracker.get_stats("2.2.2.2").requests_per_second() >= 1.0);
+    }
+
     #[test]
     fn test_rate_stats_coarse_states() {
         let stats = RateStats::new(100);
@@ -744,4 +1312,57 @@ mod tests {
         assert!(enterprise.drop_enabled);
         assert!(enterprise.bloom_drop);
     }
+
+    #[test]
+    fn test_streaming_classifier_decides_once_enough_bytes_arrive() {
+        let mut classifier = StreamingClassifier::new();
+        assert_eq!(classifier.feed(b"SS"), ClassifyState::NeedMore);
+        assert_eq!(classifier.feed(b"H-2.0-"), ClassifyState::Decided(Protocol::SSH));
+    }
+
+    #[test]
+    fn test_streaming_classifier_gives_up_at_cap() {
+        let mut classifier = StreamingClassifier::new();
+        let filler = vec![b'x'; STREAMING_BUFFER_CAP];
+        assert_eq!(classifier.feed(&filler), ClassifyState::GiveUp);
+        // Once given up, stays given up rather than re-scanning forever.
+        assert_eq!(classifier.feed(b"SSH-"), ClassifyState::GiveUp);
+    }
+
+    #[test]
+    fn test_streaming_classifier_tags_exploit_hint_mid_stream() {
+        let mut classifier = StreamingClassifier::new();
+        classifier.feed(b"some benign preamble ");
+        classifier.feed(b"nmap scan follows");
+        assert_eq!(classifier.tags() & tags::EXPLOIT_HINT, tags::EXPLOIT_HINT);
+    }
+
+    #[test]
+    fn test_module_registry_runs_builtins_unchanged() {
+        let mut registry = ModuleRegistry::new();
+        registry.register(Box::new(NoiseModule::new()));
+
+        let breaker = AdaptiveCircuitBreaker::new(ProfileFlags::HOME);
+        let mut out = Layer0Output::new(Protocol::Unknown);
+        registry.observe_all(Protocol::Unknown, b"metasploit payload", &mut out, &breaker, 5);
+
+        assert_eq!(out.tags & tags::EXPLOIT_HINT, tags::EXPLOIT_HINT);
+    }
+
+    #[test]
+    fn test_module_registry_work_sheds_when_degraded() {
+        let mut registry = ModuleRegistry::new();
+        registry.register(Box::new(NoiseModule::new()));
+
+        let breaker = AdaptiveCircuitBreaker::new(ProfileFlags::ENTERPRISE);
+        // Force degradation so should_skip_optional() is true.
+        breaker.degrade();
+        breaker.degrade();
+
+        let mut out = Layer0Output::new(Protocol::Unknown);
+        registry.observe_all(Protocol::Unknown, b"metasploit payload", &mut out, &breaker, 5);
+
+        // Degraded + enterprise profile: the module never ran.
+        assert_eq!(out.tags & tags::EXPLOIT_HINT, 0);
+    }
 }
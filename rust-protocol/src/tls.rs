@@ -0,0 +1,336 @@
+// TLS ClientHello parsing and JA3 fingerprinting
+// -------------------------------------------------
+// HTTPS/TLS probes carry the richest client signal available. This
+// detects a TLS ClientHello (record type 0x16, handshake type 0x01),
+// parses out the fields JA3 cares about, and computes the standard JA3
+// fingerprint. Nothing outside this module calls it:
+// `classify_protocol_fast` still returns `Protocol::Unknown` for a TLS
+// record, so TLS clients are not routed on their fingerprint.
+
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum TlsParseError {
+    NotTls,
+    NotClientHello,
+    Truncated,
+}
+
+impl fmt::Display for TlsParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TlsParseError::NotTls => write!(f, "not a TLS record (type byte != 0x16)"),
+            TlsParseError::NotClientHello => write!(f, "TLS handshake is not a ClientHello"),
+            TlsParseError::Truncated => write!(f, "ClientHello is shorter than its own length fields claim"),
+        }
+    }
+}
+
+impl std::error::Error for TlsParseError {}
+
+/// Quick, allocation-free check for whether a buffer opens with a TLS
+/// record carrying a ClientHello, without fully parsing it.
+pub fn is_tls_client_hello(data: &[u8]) -> bool {
+    data.len() >= 6 && data[0] == 0x16 && data[5] == 0x01
+}
+
+/// The subset of ClientHello fields JA3 is computed from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientHello {
+    pub version: u16,
+    pub cipher_suites: Vec<u16>,
+    pub extensions: Vec<u16>,
+    pub supported_groups: Vec<u16>,
+    pub ec_point_formats: Vec<u8>,
+}
+
+/// Bounds-checked big-endian cursor (TLS is a network-byte-order format).
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], TlsParseError> {
+        let end = self.pos.checked_add(len).ok_or(TlsParseError::Truncated)?;
+        if end > self.data.len() {
+            return Err(TlsParseError::Truncated);
+        }
+        let slice = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn take_u8(&mut self) -> Result<u8, TlsParseError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn take_u16(&mut self) -> Result<u16, TlsParseError> {
+        let b = self.take(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn take_u24(&mut self) -> Result<u32, TlsParseError> {
+        let b = self.take(3)?;
+        Ok(u32::from_be_bytes([0, b[0], b[1], b[2]]))
+    }
+}
+
+fn parse_u16_list(bytes: &[u8]) -> Vec<u16> {
+    bytes.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect()
+}
+
+/// Parse a ClientHello out of a record buffer. Every length prefix is
+/// bounds-checked before slicing, so truncated input returns an error
+/// rather than panicking.
+pub fn parse_client_hello(data: &[u8]) -> Result<ClientHello, TlsParseError> {
+    if data.len() < 5 {
+        return Err(TlsParseError::Truncated);
+    }
+    if data[0] != 0x16 {
+        return Err(TlsParseError::NotTls);
+    }
+
+    // Record header: content type (1) + version (2) + length (2).
+    let mut r = Reader::new(&data[5..]);
+
+    let handshake_type = r.take_u8()?;
+    if handshake_type != 0x01 {
+        return Err(TlsParseError::NotClientHello);
+    }
+    let _handshake_len = r.take_u24()?;
+
+    let version = r.take_u16()?;
+    let _random = r.take(32)?;
+
+    let session_id_len = r.take_u8()? as usize;
+    let _session_id = r.take(session_id_len)?;
+
+    let cipher_suites_len = r.take_u16()? as usize;
+    let cipher_suites = parse_u16_list(r.take(cipher_suites_len)?);
+
+    let compression_len = r.take_u8()? as usize;
+    let _compression = r.take(compression_len)?;
+
+    let mut extensions = Vec::new();
+    let mut supported_groups = Vec::new();
+    let mut ec_point_formats = Vec::new();
+
+    if r.remaining() >= 2 {
+        let extensions_len = r.take_u16()? as usize;
+        let ext_bytes = r.take(extensions_len)?;
+        let mut er = Reader::new(ext_bytes);
+
+        while er.remaining() >= 4 {
+            let ext_type = er.take_u16()?;
+            let ext_len = er.take_u16()? as usize;
+            let ext_data = er.take(ext_len)?;
+            extensions.push(ext_type);
+
+            match ext_type {
+                // supported_groups (elliptic curves)
+                0x000a if ext_data.len() >= 2 => {
+                    let list_len = u16::from_be_bytes([ext_data[0], ext_data[1]]) as usize;
+                    let list = &ext_data[2..ext_data.len().min(2 + list_len)];
+                    supported_groups = parse_u16_list(list);
+                }
+                // ec_point_formats
+                0x000b if !ext_data.is_empty() => {
+                    let list_len = ext_data[0] as usize;
+                    let list = &ext_data[1..ext_data.len().min(1 + list_len)];
+                    ec_point_formats = list.to_vec();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(ClientHello {
+        version,
+        cipher_suites,
+        extensions,
+        supported_groups,
+        ec_point_formats,
+    })
+}
+
+/// GREASE values (RFC 8701) are reserved placeholders like 0x0A0A,
+/// 0x1A1A, ... 0xFAFA that real clients send to exercise unknown-value
+/// handling. JA3 strips them so two runs of the same client don't
+/// produce different fingerprints.
+fn is_grease(value: u16) -> bool {
+    let hi = (value >> 8) as u8;
+    let lo = (value & 0xff) as u8;
+    hi == lo && (hi & 0x0f) == 0x0a
+}
+
+fn strip_grease(values: &[u16]) -> Vec<u16> {
+    values.iter().copied().filter(|v| !is_grease(*v)).collect()
+}
+
+fn join_dash<T: ToString>(values: &[T]) -> String {
+    values.iter().map(ToString::to_string).collect::<Vec<_>>().join("-")
+}
+
+/// Build the JA3 string: five comma-joined fields (version, ciphers,
+/// extensions, supported groups, EC point formats), each a dash-joined
+/// decimal list, with GREASE stripped from ciphers/extensions/groups.
+pub fn ja3_string(hello: &ClientHello) -> String {
+    format!(
+        "{},{},{},{},{}",
+        hello.version,
+        join_dash(&strip_grease(&hello.cipher_suites)),
+        join_dash(&strip_grease(&hello.extensions)),
+        join_dash(&strip_grease(&hello.supported_groups)),
+        join_dash(&hello.ec_point_formats),
+    )
+}
+
+/// MD5 of the JA3 string, the conventional JA3 fingerprint hash.
+pub fn ja3_hash(hello: &ClientHello) -> String {
+    format!("{:x}", md5::compute(ja3_string(hello).as_bytes()))
+}
+
+/// A small embedded set of JA3 hashes seen from known scanners/malware.
+/// A cheap Layer 0 hint, not a complete threat feed; the set is compiled
+/// in and nothing extends it at runtime.
+const KNOWN_BAD_JA3: &[(&str, &str)] = &[
+    ("e7d705a3286e19ea42f587b344ee6865", "masscan"),
+    ("6734f37431670b3ab4292b8f60f29984", "generic scanner stack"),
+];
+
+/// Look up a JA3 hash against the embedded known-bad set.
+pub fn classify_ja3(hash: &str) -> Option<&'static str> {
+    KNOWN_BAD_JA3.iter().find(|(h, _)| *h == hash).map(|(_, label)| *label)
+}
+
+/// Fold a JA3 verdict into a `Layer0Output`: tags `EXPLOIT_HINT` for a
+/// matched scanner/malware fingerprint, `PROBABLE_NOISE` otherwise just
+/// for having a fingerprint at all (cheap hint, not a verdict).
+pub fn apply_to_layer0(hash: &str, out: &mut crate::reducers::Layer0Output) {
+    use crate::reducers::tags;
+
+    if let Some(_label) = classify_ja3(hash) {
+        out.add_tag(tags::EXPLOIT_HINT);
+        out.add_score(40);
+    } else {
+        out.add_tag(tags::PROBABLE_NOISE);
+    }
+}
+
+/// A JA3 hash is stable across re-probes from the same tool, unlike raw
+/// payload bytes, so route `VerdictCache` lookups through it instead.
+pub fn verdict_cache_key(ja3_hash: &str) -> u64 {
+    crate::reducers::VerdictCache::cache_key(ja3_hash, &[])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hand-built minimal ClientHello: TLS 1.2, a GREASE cipher plus one
+    /// real cipher, a supported_groups extension with one GREASE curve and
+    /// one real curve, and an ec_point_formats extension.
+    fn sample_client_hello_bytes() -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&0x0303u16.to_be_bytes()); // client_version TLS 1.2
+        body.extend_from_slice(&[0u8; 32]); // random
+        body.push(0); // session_id_len = 0
+
+        let ciphers: [u16; 2] = [0x0a0a, 0x1301]; // GREASE + TLS_AES_128_GCM_SHA256
+        body.extend_from_slice(&((ciphers.len() * 2) as u16).to_be_bytes());
+        for c in ciphers {
+            body.extend_from_slice(&c.to_be_bytes());
+        }
+
+        body.push(1); // compression methods len
+        body.push(0); // null compression
+
+        // Extensions
+        let mut extensions = Vec::new();
+
+        // supported_groups: GREASE + x25519 (0x001d)
+        let mut groups_ext = Vec::new();
+        let groups: [u16; 2] = [0x2a2a, 0x001d];
+        groups_ext.extend_from_slice(&((groups.len() * 2) as u16).to_be_bytes());
+        for g in groups {
+            groups_ext.extend_from_slice(&g.to_be_bytes());
+        }
+        extensions.extend_from_slice(&0x000au16.to_be_bytes());
+        extensions.extend_from_slice(&(groups_ext.len() as u16).to_be_bytes());
+        extensions.extend_from_slice(&groups_ext);
+
+        // ec_point_formats: uncompressed (0)
+        let points_ext = vec![1u8, 0u8];
+        extensions.extend_from_slice(&0x000bu16.to_be_bytes());
+        extensions.extend_from_slice(&(points_ext.len() as u16).to_be_bytes());
+        extensions.extend_from_slice(&points_ext);
+
+        body.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+        body.extend_from_slice(&extensions);
+
+        let mut handshake = Vec::new();
+        handshake.push(0x01); // ClientHello
+        let len = body.len() as u32;
+        handshake.extend_from_slice(&len.to_be_bytes()[1..]); // u24 length
+        handshake.extend_from_slice(&body);
+
+        let mut record = Vec::new();
+        record.push(0x16); // handshake content type
+        record.extend_from_slice(&0x0301u16.to_be_bytes()); // record version
+        record.extend_from_slice(&(handshake.len() as u16).to_be_bytes());
+        record.extend_from_slice(&handshake);
+        record
+    }
+
+    #[test]
+    fn detects_client_hello_prefix() {
+        let record = sample_client_hello_bytes();
+        assert!(is_tls_client_hello(&record));
+        assert!(!is_tls_client_hello(b"GET / HTTP/1.1"));
+    }
+
+    #[test]
+    fn parses_fields_and_strips_grease_in_ja3() {
+        let record = sample_client_hello_bytes();
+        let hello = parse_client_hello(&record).expect("valid ClientHello parses");
+
+        assert_eq!(hello.version, 0x0303);
+        assert_eq!(hello.cipher_suites, vec![0x0a0a, 0x1301]);
+        assert_eq!(hello.supported_groups, vec![0x2a2a, 0x001d]);
+        assert_eq!(hello.ec_point_formats, vec![0]);
+
+        let ja3 = ja3_string(&hello);
+        // 771 = 0x0303; GREASE cipher and GREASE group are stripped; the
+        // extension field lists supported_groups (10) and ec_point_formats (11).
+        assert_eq!(ja3, "771,4865,10-11,29,0");
+    }
+
+    #[test]
+    fn rejects_truncated_input_without_panicking() {
+        let record = sample_client_hello_bytes();
+        for cut in 0..record.len() {
+            let _ = parse_client_hello(&record[..cut]);
+        }
+        assert_eq!(parse_client_hello(&record[..4]), Err(TlsParseError::Truncated));
+    }
+
+    #[test]
+    fn rejects_non_tls_input() {
+        assert_eq!(parse_client_hello(b"GET / HTTP/1.1"), Err(TlsParseError::NotTls));
+    }
+
+    #[test]
+    fn known_bad_ja3_is_classified() {
+        assert_eq!(classify_ja3("e7d705a3286e19ea42f587b344ee6865"), Some("masscan"));
+        assert_eq!(classify_ja3("not-a-real-hash"), None);
+    }
+}
@@ -37,22 +37,24 @@ pub fn extract_ip(input: &str) -> Option<String> {
     None
 }
 
-/// Calculate entropy of a string (useful for detecting random/encrypted data)
-pub fn calculate_entropy(data: &str) -> f64 {
+/// Calculate Shannon entropy of a byte slice (useful for detecting
+/// random/encrypted data). Operates on bytes so it agrees with
+/// `classify_payload`, which needs the raw histogram anyway.
+pub fn calculate_entropy(data: &[u8]) -> f64 {
     use std::collections::HashMap;
-    
+
     if data.is_empty() {
         return 0.0;
     }
-    
+
     let mut frequency = HashMap::new();
-    for byte in data.bytes() {
-        *frequency.entry(byte).or_insert(0) += 1;
+    for byte in data {
+        *frequency.entry(*byte).or_insert(0) += 1;
     }
-    
+
     let len = data.len() as f64;
     let mut entropy = 0.0;
-    
+
     for count in frequency.values() {
         let probability = *count as f64 / len;
         entropy -= probability * probability.log2();
@@ -61,6 +63,141 @@ pub fn calculate_entropy(data: &str) -> f64 {
     entropy
 }
 
+/// Fixed-size window used when scanning for a high-entropy blob embedded
+/// in an otherwise low-entropy (mostly text) payload.
+const CLASSIFY_WINDOW: usize = 256;
+
+/// Coarse classification of a payload's likely encoding, combining several
+/// cheap single-pass statistics so that encrypted/compressed/base64/plain
+/// buffers don't all collapse into one entropy number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadClass {
+    Plaintext,
+    Base64,
+    Hex,
+    Compressed,
+    Encrypted,
+    Unknown,
+}
+
+/// Chi-square statistic over the 256-byte histogram against a uniform
+/// distribution. Low values mean the byte distribution looks uniform
+/// (consistent with encryption); high values mean it's structured.
+fn chi_square_uniformity(data: &[u8]) -> f64 {
+    let mut histogram = [0u64; 256];
+    for &byte in data {
+        histogram[byte as usize] += 1;
+    }
+
+    let len = data.len() as f64;
+    let expected = len / 256.0;
+    if expected == 0.0 {
+        return 0.0;
+    }
+
+    histogram
+        .iter()
+        .map(|&count| {
+            let diff = count as f64 - expected;
+            diff * diff / expected
+        })
+        .sum()
+}
+
+fn printable_ascii_ratio(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+
+    let printable = data
+        .iter()
+        .filter(|&&b| (0x20..=0x7e).contains(&b) || b == b'\n' || b == b'\r' || b == b'\t')
+        .count();
+
+    printable as f64 / data.len() as f64
+}
+
+fn is_likely_hex(data: &[u8]) -> bool {
+    if data.is_empty() || data.len() % 2 != 0 {
+        return false;
+    }
+
+    data.iter().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Min/max Shannon entropy across non-overlapping `CLASSIFY_WINDOW`-byte
+/// windows, so a high-entropy blob embedded in mostly-plaintext data
+/// shows up even when the whole-buffer entropy looks unremarkable.
+fn windowed_entropy_range(data: &[u8]) -> (f64, f64) {
+    if data.len() <= CLASSIFY_WINDOW {
+        let entropy = calculate_entropy(data);
+        return (entropy, entropy);
+    }
+
+    let mut min = f64::MAX;
+    let mut max = f64::MIN;
+
+    for window in data.chunks(CLASSIFY_WINDOW) {
+        let entropy = calculate_entropy(window);
+        min = min.min(entropy);
+        max = max.max(entropy);
+    }
+
+    (min, max)
+}
+
+/// Classify a payload's likely encoding from cheap, single-pass statistics:
+/// full-buffer Shannon entropy, chi-square histogram uniformity,
+/// printable-ASCII ratio, and windowed min/max entropy.
+///
+/// Thresholds (tuned empirically, not statistically rigorous):
+/// - hex: every byte is an ASCII hex digit and the length is even
+/// - encrypted: high entropy (>= 7.5) AND a near-uniform histogram
+///   (chi-square below the buffer-size-scaled uniform threshold), OR a
+///   high-entropy window embedded in an otherwise unremarkable buffer
+/// - compressed: high entropy (>= 6.5) but a structured (non-uniform)
+///   histogram, since compression leaves header/block patterns that
+///   encryption does not
+/// - base64: passes `is_likely_base64` with mid-range entropy (4.5-6.0),
+///   the band real base64 text occupies
+/// - plaintext: mostly printable ASCII with low entropy (< 5.0)
+pub fn classify_payload(data: &[u8]) -> PayloadClass {
+    if data.is_empty() {
+        return PayloadClass::Unknown;
+    }
+
+    if is_likely_hex(data) {
+        return PayloadClass::Hex;
+    }
+
+    let entropy = calculate_entropy(data);
+    let chi_square = chi_square_uniformity(data);
+    // For a uniform distribution over 256 bins, chi-square has ~255
+    // degrees of freedom; scale the "looks uniform" cutoff with buffer
+    // size so short buffers aren't unfairly flagged as structured.
+    let uniform_cutoff = 255.0 + 4.0 * (data.len() as f64).sqrt();
+    let (_, window_max_entropy) = windowed_entropy_range(data);
+
+    if (entropy >= 7.5 && chi_square <= uniform_cutoff) || window_max_entropy >= 7.8 {
+        return PayloadClass::Encrypted;
+    }
+
+    if entropy >= 6.5 {
+        return PayloadClass::Compressed;
+    }
+
+    let as_text = String::from_utf8_lossy(data);
+    if is_likely_base64(&as_text) && (4.5..6.0).contains(&entropy) {
+        return PayloadClass::Base64;
+    }
+
+    if printable_ascii_ratio(data) >= 0.9 && entropy < 5.0 {
+        return PayloadClass::Plaintext;
+    }
+
+    PayloadClass::Unknown
+}
+
 /// Check if data looks like base64 encoded content
 pub fn is_likely_base64(data: &str) -> bool {
     if data.is_empty() || data.len() % 4 != 0 {
@@ -127,8 +264,8 @@ mod tests {
 
     #[test]
     fn test_calculate_entropy() {
-        assert!(calculate_entropy("aaaa") < calculate_entropy("abcd"));
-        assert_eq!(calculate_entropy(""), 0.0);
+        assert!(calculate_entropy(b"aaaa") < calculate_entropy(b"abcd"));
+        assert_eq!(calculate_entropy(b""), 0.0);
     }
 
     #[test]
@@ -144,4 +281,34 @@ mod tests {
         assert!(!is_suspicious_port(80));
         assert!(!is_suspicious_port(443));
     }
+
+    #[test]
+    fn test_classify_payload_plaintext() {
+        let payload = b"GET /index.html HTTP/1.1\r\nHost: example.com\r\n\r\n";
+        assert_eq!(classify_payload(payload), PayloadClass::Plaintext);
+    }
+
+    #[test]
+    fn test_classify_payload_hex() {
+        assert_eq!(classify_payload(b"deadbeefcafefeed"), PayloadClass::Hex);
+    }
+
+    #[test]
+    fn test_classify_payload_base64() {
+        // 48 bytes of base64-ish text, mid-range entropy.
+        let payload = b"VGhpcyBpcyBhIHRlc3QgbWVzc2FnZSBmb3IgYmFzZTY0IQ==";
+        assert_eq!(classify_payload(payload), PayloadClass::Base64);
+    }
+
+    #[test]
+    fn test_classify_payload_encrypted_high_entropy() {
+        // Pseudo-random-looking bytes spread across the full byte range.
+        let payload: Vec<u8> = (0..=255u8).cycle().take(2048).collect();
+        assert_eq!(classify_payload(&payload), PayloadClass::Encrypted);
+    }
+
+    #[test]
+    fn test_classify_payload_empty_is_unknown() {
+        assert_eq!(classify_payload(b""), PayloadClass::Unknown);
+    }
 }